@@ -2,8 +2,8 @@
 use crate::*;
 use near_sdk::{ext_contract, json_types::U64};
 
-// Define the trait for NonFungibleTokenCore
-pub trait NonFungibleTokenCore {
+// Define the trait for NonFungibleTokenApproval
+pub trait NonFungibleTokenApproval {
     // Approve an account ID to transfer a token on your behalf
     fn nft_approve(&mut self, token_id: U64, account_id: AccountId, msg: Option<String>);
 
@@ -24,7 +24,7 @@ pub trait NonFungibleTokenCore {
 
 // External contract trait for NonFungibleTokenApprovalsReceiver
 #[ext_contract(ext_non_fungible_approval_receiver)]
-trait NonFungibleTokenApprovalsReceiver {
+pub trait NonFungibleTokenApprovalsReceiver {
     // Cross-contract call to an external contract that is initiated during nft_approve
     fn nft_on_approve(
         &mut self,
@@ -35,9 +35,9 @@ trait NonFungibleTokenApprovalsReceiver {
     );
 }
 
-// Implement NonFungibleTokenCore for the Contract
+// Implement NonFungibleTokenApproval for the Contract
 #[near_bindgen]
-impl NonFungibleTokenCore for Contract {
+impl NonFungibleTokenApproval for Contract {
     // Allow a specific account ID to approve a token on your behalf
     #[payable]
     fn nft_approve(&mut self, token_id: U64, account_id: AccountId, msg: Option<String>) {
@@ -54,6 +54,16 @@ impl NonFungibleTokenCore for Contract {
             "Predecessor must be the token owner."
         );
 
+        // Tickets that can't be transferred can't be approved for transfer either
+        let raffle = self
+            .raffle_by_id
+            .get(&ticket.raffle_id)
+            .expect("Not a raffle");
+        require!(
+            raffle.transferable,
+            "Tickets for this raffle are not transferable"
+        );
+
         // Get the next approval ID if we need a new approval
         let approval_id: u64 = ticket.next_approval_id;
 
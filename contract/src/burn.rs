@@ -0,0 +1,209 @@
+use crate::*;
+use near_sdk::json_types::U64;
+
+#[near_bindgen]
+impl Contract {
+    // Burn a ticket owned by the caller and refund the storage it used to whoever paid for it.
+    // Tickets can't be burned while their raffle is being drawn, since the draw depends on ticket order.
+    #[payable]
+    pub fn nft_burn(&mut self, token_id: U64) {
+        // Ensure the user attached exactly 1 yoctoNEAR for security and wallet redirection.
+        assert_one_yocto();
+
+        // Measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        let owner_id = env::predecessor_account_id();
+        let ticket = self.ticket_by_id.get(&token_id.0).expect("No token");
+        require!(
+            ticket.owner_id == owner_id,
+            "Only the ticket owner can burn it"
+        );
+
+        let mut raffle = self
+            .raffle_by_id
+            .get(&ticket.raffle_id)
+            .expect("Not a raffle");
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Draft
+                    | RaffleStatus::Open
+                    | RaffleStatus::Settled
+                    | RaffleStatus::Cancelled
+            ),
+            "Tickets can't be burned while the raffle is being drawn"
+        );
+        if raffle_status(&raffle) == RaffleStatus::Cancelled {
+            require!(
+                self.ticket_payments.get(&token_id.0).is_none(),
+                "Refund the ticket before burning it"
+            );
+        }
+
+        // Winning tickets already stopped being live when they were drawn
+        if !raffle
+            .winners
+            .iter()
+            .any(|winner| winner.ticket_id == token_id)
+        {
+            raffle.metadata.live_tickets = raffle.metadata.live_tickets.saturating_sub(1);
+        }
+        raffle.tickets.remove(&token_id.0);
+        self.raffle_by_id.insert(&ticket.raffle_id, &raffle);
+
+        self.internal_remove_tickets_from_owner(ticket.raffle_id, &owner_id, &vec![token_id.0]);
+        self.ticket_by_id.remove(&token_id.0);
+        self.ticket_payments.remove(&token_id.0);
+        self.keypom_key_by_ticket.remove(&token_id.0);
+        let storage_payer = self.storage_payer_by_ticket.remove(&token_id.0);
+
+        // Construct the burn log as per the events standard.
+        let nft_burn_log: EventLog = EventLog {
+            // Standard name ("nep171")
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0")
+            version: NFT_METADATA_SPEC.to_string(),
+            // The data related with the event stored in a vector
+            event: EventLogVariant::NftBurn(vec![NftBurnLog {
+                // Owner of the ticket
+                owner_id: owner_id.to_string(),
+                // The owner burned the ticket themselves
+                authorized_id: None,
+                // Vector of ticket IDs that were burned
+                token_ids: vec![token_id.0.to_string()],
+                // An optional memo to include
+                memo: None,
+            }]),
+        };
+
+        // Log the serialized JSON
+        env::log_str(&nft_burn_log.to_string());
+
+        // Refund the storage that was released to the account that paid for it. Storage the
+        // contract paid for stays with the contract. The attached yoctoNEAR goes back to the owner.
+        let released_storage = initial_storage_usage - env::storage_usage();
+        let storage_refund = env::storage_byte_cost() * Balance::from(released_storage);
+        match storage_payer {
+            Some(storage_payer) if storage_payer == owner_id => {
+                Promise::new(owner_id).transfer(storage_refund + 1);
+            }
+            Some(storage_payer) => {
+                Promise::new(storage_payer).transfer(storage_refund);
+                Promise::new(owner_id).transfer(1);
+            }
+            None => {
+                Promise::new(owner_id).transfer(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::get_created_receipts;
+
+    // Every NEAR transfer the last call made, along with its receiver
+    fn transfers() -> Vec<(AccountId, Balance)> {
+        get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver_id = receipt.receiver_id;
+                receipt
+                    .actions
+                    .into_iter()
+                    .filter_map(move |action| match action {
+                        VmAction::Transfer { deposit } => Some((receiver_id.clone(), deposit)),
+                        _ => None,
+                    })
+            })
+            .collect()
+    }
+
+    fn burn(contract: &mut Contract, owner_id: &AccountId, token_id: u64) {
+        set_caller(owner_id, 1);
+        contract.nft_burn(U64(token_id));
+    }
+
+    #[test]
+    fn storage_is_refunded_to_the_minter() {
+        let mut contract = setup_open_raffle();
+        mint(&mut contract, &user(0), 1);
+        burn(&mut contract, &user(0), 0);
+
+        let transfers = transfers();
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].0, minter());
+        assert!(transfers[0].1 > 0);
+        assert_eq!(transfers[1], (user(0), 1));
+    }
+
+    #[test]
+    fn storage_is_refunded_to_the_buyer() {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), 0);
+        contract.set_ticket_price(U64(RAFFLE_ID), Some(U128(1)), None);
+        set_caller(&user(0), STORAGE_DEPOSIT);
+        contract.buy_tickets(U64(RAFFLE_ID), 1);
+        burn(&mut contract, &user(0), 0);
+
+        let transfers = transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].0, user(0));
+        assert!(transfers[0].1 > 1);
+    }
+
+    #[test]
+    fn storage_is_refunded_to_the_token_buyer() {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), 0);
+        contract.set_ft_ticket_price(
+            U64(RAFFLE_ID),
+            Some(FtTicketPrice {
+                token_id: prize_token(),
+                price: U128(100),
+            }),
+        );
+        set_caller(&user(0), STORAGE_DEPOSIT);
+        contract.storage_deposit(None);
+        set_caller(&prize_token(), 0);
+        contract.ft_on_transfer(
+            user(0),
+            U128(100),
+            format!(r#"{{"raffle_id": "{}", "quantity": 1}}"#, RAFFLE_ID),
+        );
+        burn(&mut contract, &user(0), 0);
+
+        let transfers = transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].0, user(0));
+        assert!(transfers[0].1 > 1);
+    }
+
+    #[test]
+    fn storage_the_contract_paid_for_is_kept() {
+        let mut contract = setup_open_raffle();
+        mint(&mut contract, &user(0), 1);
+        // Tickets minted before storage payers were recorded have none
+        contract.storage_payer_by_ticket.remove(&0);
+        burn(&mut contract, &user(0), 0);
+
+        assert_eq!(transfers(), vec![(user(0), 1)]);
+    }
+
+    #[test]
+    fn transferred_tickets_refund_the_original_payer() {
+        let mut contract = setup_open_raffle();
+        mint(&mut contract, &user(0), 1);
+        set_caller(&user(0), 1);
+        contract.nft_transfer(user(1), U64(0), None, None);
+        burn(&mut contract, &user(1), 0);
+
+        let transfers = transfers();
+        assert_eq!(transfers[0].0, minter());
+        assert_eq!(transfers[1], (user(1), 1));
+    }
+}
@@ -0,0 +1,442 @@
+use crate::*;
+use near_sdk::{json_types::U64, Gas, PromiseResult};
+
+// Define gas constants for returning prizes and payments and resolving the transfer
+const GAS_FOR_NFT_RETURN: Gas = Gas(15_000_000_000_000);
+const GAS_FOR_FT_RETURN: Gas = Gas(15_000_000_000_000);
+const GAS_FOR_RESOLVE_RETURN: Gas = Gas(10_000_000_000_000);
+
+#[near_bindgen]
+impl Contract {
+    // Call off a raffle that hasn't been drawn yet. No more tickets can be minted and it can't be drawn.
+    // Depositors can then reclaim their prizes and buyers can get back what they paid for their tickets.
+    pub fn cancel_raffle(&mut self, raffle_id: U64) {
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        self.assert_raffle_owner(&raffle);
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Draft | RaffleStatus::Open | RaffleStatus::Closed
+            ),
+            "Raffle has already been drawn"
+        );
+
+        raffle.status = RaffleStatus::Cancelled;
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+
+        // Cancelled raffles no longer count towards the creator's active raffles
+        self.internal_remove_active_raffle(&raffle.owner_id);
+
+        env::log_str(&format!("Raffle {} was cancelled", raffle_id.0));
+    }
+
+    // Return an escrowed NFT prize of a cancelled raffle, or one nobody claimed from a settled
+    // raffle, to the account that deposited it
+    pub fn reclaim_nft_prize(&mut self, raffle_id: U64, prize_index: u64) -> Promise {
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Cancelled | RaffleStatus::Settled
+            ),
+            "Raffle is not cancelled or settled"
+        );
+        let prize = raffle
+            .nft_prizes
+            .get_mut(prize_index as usize)
+            .expect("No NFT prize at this index");
+        require!(
+            env::predecessor_account_id() == prize.depositor_id,
+            "Only the depositor can reclaim this prize"
+        );
+        require!(!prize.claimed, "Prize already returned");
+
+        // Mark the prize as claimed before sending it so it can't be returned twice
+        prize.claimed = true;
+        let prize = prize.clone();
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+
+        ext_nft_prize::ext(prize.nft_contract_id)
+            .with_attached_deposit(1)
+            .with_static_gas(GAS_FOR_NFT_RETURN)
+            .nft_transfer(prize.depositor_id, prize.token_id, None, None)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_RETURN)
+                    .resolve_nft_prize_claim(raffle_id, prize_index),
+            )
+    }
+
+    // Return the caller's deposit into a cancelled raffle's prize pool for the given token. For a
+    // settled raffle, each depositor gets back their share of what the winners didn't claim.
+    pub fn reclaim_ft_prize(&mut self, raffle_id: U64, token_id: AccountId) -> Promise {
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Cancelled | RaffleStatus::Settled
+            ),
+            "Raffle is not cancelled or settled"
+        );
+        let depositor_id = env::predecessor_account_id();
+        let unclaimed = unclaimed_ft_prize(&raffle, &token_id);
+        let pool = raffle
+            .ft_prize_pools
+            .get_mut(&token_id)
+            .expect("No prize pool for this token");
+
+        // Remove the depositor before sending the tokens so they can't be returned twice. The pool
+        // keeps its total so every depositor's share is worked out the same way.
+        let deposited = pool
+            .depositors
+            .remove(&depositor_id)
+            .expect("Nothing deposited by this account");
+        let amount = U128(mul_div(deposited.0, unclaimed, pool.amount.0));
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+        require!(amount.0 > 0, "Nothing left to return to this account");
+
+        ext_ft_prize::ext(token_id.clone())
+            .with_attached_deposit(1)
+            .with_static_gas(GAS_FOR_FT_RETURN)
+            .ft_transfer(
+                depositor_id.clone(),
+                amount,
+                Some(format!("Raffle {} prize returned", raffle_id.0)),
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_RETURN)
+                    .resolve_ft_prize_reclaim(raffle_id, token_id, depositor_id, deposited),
+            )
+    }
+
+    // Resolve the token transfer from reclaim_ft_prize. If it failed, the depositor can reclaim again.
+    #[private]
+    pub fn resolve_ft_prize_reclaim(
+        &mut self,
+        raffle_id: U64,
+        token_id: AccountId,
+        depositor_id: AccountId,
+        amount: U128,
+    ) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        if let Some(pool) = raffle.ft_prize_pools.get_mut(&token_id) {
+            pool.depositors.insert(depositor_id, amount);
+        }
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+
+        false
+    }
+
+    // Return a cancelled raffle's NEAR prize pool, or what the winners of a settled raffle didn't
+    // claim from it, to the raffle owner, who funded it
+    pub fn reclaim_near_prize(&mut self, raffle_id: U64) -> Promise {
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        self.assert_raffle_owner(&raffle);
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Cancelled | RaffleStatus::Settled
+            ),
+            "Raffle is not cancelled or settled"
+        );
+        let amount = unclaimed_near_prize(&raffle);
+        require!(amount > 0, "No NEAR prize pool to return");
+
+        // Take the unclaimed NEAR out of the pool before sending it so it can't be returned twice
+        raffle.near_prize_pool -= amount;
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+
+        Promise::new(raffle.owner_id).transfer(amount).then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_RESOLVE_RETURN)
+                .resolve_near_prize_reclaim(raffle_id, U128(amount)),
+        )
+    }
+
+    // Resolve the transfer from reclaim_near_prize. If it failed, the NEAR goes back in the pool.
+    #[private]
+    pub fn resolve_near_prize_reclaim(&mut self, raffle_id: U64, amount: U128) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        raffle.near_prize_pool += amount.0;
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+
+        false
+    }
+
+    // Refund what was paid for a ticket of a cancelled raffle to the ticket's current owner
+    pub fn refund_ticket(&mut self, token_id: U64) -> Promise {
+        let ticket = self.ticket_by_id.get(&token_id.0).expect("No ticket");
+        require!(
+            env::predecessor_account_id() == ticket.owner_id,
+            "Only the ticket owner can get a refund"
+        );
+
+        let mut raffle = self
+            .raffle_by_id
+            .get(&ticket.raffle_id)
+            .expect("Not a raffle");
+        require!(
+            raffle_status(&raffle) == RaffleStatus::Cancelled,
+            "Raffle is not cancelled"
+        );
+
+        // Forget the payment before refunding it so it can't be refunded twice
+        let payment = self
+            .ticket_payments
+            .remove(&token_id.0)
+            .expect("Ticket was not paid for or was already refunded");
+
+        // Record the shares the charities and the beneficiary give back
+        log_donation_refund(
+            ticket.raffle_id,
+            &ticket.owner_id,
+            payment.token_id.as_ref(),
+            &proceeds_payouts(&raffle, payment.amount.0),
+        );
+
+        let refund = match payment.token_id.as_ref() {
+            None => {
+                raffle.near_proceeds -= payment.amount.0;
+                Promise::new(ticket.owner_id).transfer(payment.amount.0)
+            }
+            Some(payment_token_id) => {
+                *raffle
+                    .ft_proceeds
+                    .get_mut(payment_token_id)
+                    .expect("No proceeds for this token") -= payment.amount.0;
+                ext_ft_prize::ext(payment_token_id.clone())
+                    .with_attached_deposit(1)
+                    .with_static_gas(GAS_FOR_FT_RETURN)
+                    .ft_transfer(
+                        ticket.owner_id,
+                        payment.amount,
+                        Some(format!("Raffle {} cancelled", ticket.raffle_id)),
+                    )
+            }
+        };
+        self.raffle_by_id.insert(&ticket.raffle_id, &raffle);
+
+        refund.then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_RESOLVE_RETURN)
+                .resolve_ticket_refund(token_id, payment),
+        )
+    }
+
+    // Resolve the transfer from refund_ticket. If it failed, the ticket can be refunded again.
+    #[private]
+    pub fn resolve_ticket_refund(&mut self, token_id: U64, payment: TicketPayment) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+
+        let ticket = self.ticket_by_id.get(&token_id.0).expect("No ticket");
+        let mut raffle = self
+            .raffle_by_id
+            .get(&ticket.raffle_id)
+            .expect("Not a raffle");
+        match payment.token_id.as_ref() {
+            None => raffle.near_proceeds += payment.amount.0,
+            Some(payment_token_id) => {
+                *raffle
+                    .ft_proceeds
+                    .entry(payment_token_id.clone())
+                    .or_insert(0) += payment.amount.0
+            }
+        }
+        self.raffle_by_id.insert(&ticket.raffle_id, &raffle);
+        self.ticket_payments.insert(&token_id.0, &payment);
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::get_logs;
+
+    const TICKET_PRICE: Balance = 1_000;
+
+    fn cancel(contract: &mut Contract) {
+        set_caller(&raffle_owner(), 0);
+        contract.cancel_raffle(U64(RAFFLE_ID));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the raffle owner can fund the prize pool")]
+    fn contract_owner_cannot_fund_the_prize_pool() {
+        let mut contract = setup_open_raffle();
+        set_caller(&contract_owner(), 500);
+        contract.fund_raffle(U64(RAFFLE_ID));
+    }
+
+    #[test]
+    fn reclaim_near_prize_after_cancelling() {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), 500);
+        contract.fund_raffle(U64(RAFFLE_ID));
+        cancel(&mut contract);
+
+        set_caller(&raffle_owner(), 0);
+        contract.reclaim_near_prize(U64(RAFFLE_ID));
+        assert_eq!(raffle(&contract).near_prize_pool, 0);
+
+        // A failed transfer puts the NEAR back in the pool so it can be reclaimed again
+        set_callback_result(PromiseResult::Failed);
+        assert!(!contract.resolve_near_prize_reclaim(U64(RAFFLE_ID), U128(500)));
+        assert_eq!(raffle(&contract).near_prize_pool, 500);
+    }
+
+    #[test]
+    #[should_panic(expected = "Raffle is not cancelled or settled")]
+    fn reclaim_near_prize_requires_cancelling() {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), 500);
+        contract.fund_raffle(U64(RAFFLE_ID));
+
+        set_caller(&raffle_owner(), 0);
+        contract.reclaim_near_prize(U64(RAFFLE_ID));
+    }
+
+    #[test]
+    fn reclaim_ft_prize_after_cancelling() {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.register_prize_token(U64(RAFFLE_ID), prize_token(), U128(100));
+        set_caller(&prize_token(), 0);
+        contract.ft_on_transfer(user(0), U128(300), RAFFLE_ID.to_string());
+        cancel(&mut contract);
+
+        set_caller(&user(0), 0);
+        contract.reclaim_ft_prize(U64(RAFFLE_ID), prize_token());
+        let pool = &raffle(&contract).ft_prize_pools[&prize_token()];
+        assert!(!pool.depositors.contains_key(&user(0)));
+
+        // A failed transfer lets the depositor reclaim their tokens again
+        set_callback_result(PromiseResult::Failed);
+        assert!(!contract.resolve_ft_prize_reclaim(
+            U64(RAFFLE_ID),
+            prize_token(),
+            user(0),
+            U128(300)
+        ));
+        let pool = &raffle(&contract).ft_prize_pools[&prize_token()];
+        assert_eq!(pool.depositors.get(&user(0)), Some(&U128(300)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Nothing deposited by this account")]
+    fn reclaim_ft_prize_only_returns_own_deposit() {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.register_prize_token(U64(RAFFLE_ID), prize_token(), U128(100));
+        set_caller(&prize_token(), 0);
+        contract.ft_on_transfer(user(0), U128(300), RAFFLE_ID.to_string());
+        cancel(&mut contract);
+
+        set_caller(&user(1), 0);
+        contract.reclaim_ft_prize(U64(RAFFLE_ID), prize_token());
+    }
+
+    #[test]
+    fn reclaim_nft_prize_after_cancelling() {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.register_nft_prize(U64(RAFFLE_ID), nft_contract(), "1".to_string());
+        set_caller(&nft_contract(), 0);
+        contract.nft_on_transfer(
+            raffle_owner(),
+            raffle_owner(),
+            "1".to_string(),
+            RAFFLE_ID.to_string(),
+        );
+        cancel(&mut contract);
+
+        set_caller(&raffle_owner(), 0);
+        contract.reclaim_nft_prize(U64(RAFFLE_ID), 0);
+        assert!(raffle(&contract).nft_prizes[0].claimed);
+
+        // A failed transfer lets the depositor reclaim the NFT again
+        set_callback_result(PromiseResult::Failed);
+        assert!(!contract.resolve_nft_prize_claim(U64(RAFFLE_ID), 0));
+        assert!(!raffle(&contract).nft_prizes[0].claimed);
+    }
+
+    #[test]
+    fn refund_ticket_after_cancelling() {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), 0);
+        contract.set_ticket_price(U64(RAFFLE_ID), Some(U128(TICKET_PRICE)), None);
+        set_caller(&user(0), TICKET_PRICE + STORAGE_DEPOSIT);
+        let ticket_id = contract.buy_tickets(U64(RAFFLE_ID), 1)[0];
+        cancel(&mut contract);
+
+        set_caller(&user(0), 0);
+        contract.refund_ticket(ticket_id);
+        assert!(contract.ticket_payments.get(&ticket_id.0).is_none());
+        assert_eq!(raffle(&contract).near_proceeds, 0);
+        assert!(get_logs()
+            .iter()
+            .any(|log| log.contains(r#""event":"donation_refund""#)));
+
+        // A failed refund restores the payment so the ticket can be refunded again
+        let payment = TicketPayment {
+            token_id: None,
+            amount: U128(TICKET_PRICE),
+        };
+        set_callback_result(PromiseResult::Failed);
+        assert!(!contract.resolve_ticket_refund(ticket_id, payment));
+        assert!(contract.ticket_payments.get(&ticket_id.0).is_some());
+        assert_eq!(raffle(&contract).near_proceeds, TICKET_PRICE);
+    }
+
+    #[test]
+    #[should_panic(expected = "Raffle is not cancelled")]
+    fn refund_ticket_requires_cancelling() {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), 0);
+        contract.set_ticket_price(U64(RAFFLE_ID), Some(U128(TICKET_PRICE)), None);
+        set_caller(&user(0), TICKET_PRICE + STORAGE_DEPOSIT);
+        let ticket_id = contract.buy_tickets(U64(RAFFLE_ID), 1)[0];
+
+        set_caller(&user(0), 0);
+        contract.refund_ticket(ticket_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Refund the ticket before burning it")]
+    fn paid_tickets_are_refunded_before_burning() {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), 0);
+        contract.set_ticket_price(U64(RAFFLE_ID), Some(U128(TICKET_PRICE)), None);
+        set_caller(&user(0), TICKET_PRICE + STORAGE_DEPOSIT);
+        let ticket_id = contract.buy_tickets(U64(RAFFLE_ID), 1)[0];
+        cancel(&mut contract);
+
+        set_caller(&user(0), 1);
+        contract.nft_burn(ticket_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Raffle has already been drawn")]
+    fn drawn_raffles_cannot_be_cancelled() {
+        let mut contract = setup_open_raffle();
+        mint(&mut contract, &user(0), 2);
+        set_caller(&raffle_owner(), 0);
+        contract.close_raffle(U64(RAFFLE_ID));
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.draw_winners(U64(RAFFLE_ID), Some(1), None);
+        cancel(&mut contract);
+    }
+}
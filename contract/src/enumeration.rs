@@ -1,74 +1,104 @@
 use crate::nft_core::NonFungibleTokenCore;
 use crate::*;
+use near_sdk::json_types::U64;
 
-/// Struct to return in views to query for specific data related to an access key.
-#[derive(BorshDeserialize, BorshSerialize, Serialize)]
+/// Struct to return in views to query for specific data related to a raffle.
+#[derive(Serialize)]
 #[serde(crate = "near_sdk::serde")]
-pub struct JsonSeries {
-    series_id: u64,
-    mint_id: u64,
-    // Metadata including title, num copies etc.. that all tokens will derive from
-    metadata: TokenMetadata,
-    // Royalty used for all tokens in the collection
+pub struct JsonRaffle {
+    raffle_id: U64,
+    // Current lifecycle state of the raffle
+    status: RaffleStatus,
+    // Keypom drops that can mint tickets into the raffle
+    keypom_drops: Vec<KeypomDrop>,
+    // Metadata including title, max tickets etc.. that all tickets will derive from
+    metadata: RaffleMetadata,
+    // How many more tickets can be minted, if the raffle has a cap
+    remaining_tickets: Option<U64>,
+    // Royalty used for all tickets in the raffle
     royalty: Option<HashMap<AccountId, u32>>,
-    // Owner of the collection (they can update collection ID)
+    // Whether tickets in the raffle can be transferred
+    transferable: bool,
+    // Optional start and end times of the raffle (in nanoseconds)
+    starts_at: Option<U64>,
+    ends_at: Option<U64>,
+    // Number of winners the raffle draws, if it was set when the raffle was created
+    num_winners: Option<U64>,
+    // Optional time by which winners must claim their prize (in nanoseconds)
+    claim_deadline: Option<U64>,
+    // Ordered prize tiers of the raffle
+    prize_tiers: Vec<PrizeTier>,
+    // NEAR prize pool and how it is split between winners (in basis points)
+    near_prize_pool: U128,
+    prize_split: Vec<u32>,
+    // Price of a ticket bought through buy_tickets and who receives the proceeds
+    ticket_price: Option<U128>,
+    ft_ticket_price: Option<FtTicketPrice>,
+    beneficiary_id: Option<AccountId>,
+    charity_split: HashMap<AccountId, u32>,
+    // Ticket sale proceeds held until the raffle is drawn
+    near_proceeds: U128,
+    ft_proceeds: HashMap<AccountId, U128>,
+    // Payouts of the proceeds that failed and will be paid again on the next withdrawal
+    unpaid_proceeds: Vec<UnpaidProceeds>,
+    // Owner of the raffle
     owner_id: AccountId,
 }
 
 #[near_bindgen]
 impl Contract {
-    //Query for the total supply of NFTs on the contract
+    //Query for the total supply of tickets on the contract
     pub fn nft_total_supply(&self) -> U128 {
-        //return the length of the token metadata by ID
-        U128(self.tokens_by_id.len() as u128)
+        //return the length of the tickets by ID
+        U128(self.ticket_by_id.len() as u128)
     }
 
-    //Query for nft tokens on the contract regardless of the owner using pagination
-    pub fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonToken> {
+    //Query for tickets on the contract regardless of the owner using pagination
+    pub fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonTicket> {
         //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
         let start = u128::from(from_index.unwrap_or(U128(0)));
 
-        //iterate through each token using an iterator
-        self.tokens_by_id
+        //iterate through each ticket using an iterator
+        self.ticket_by_id
             .keys()
             //skip to the index we specified in the start variable
             .skip(start as usize)
             //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
             .take(limit.unwrap_or(50) as usize)
-            //we'll map the token IDs which are strings into Json Tokens
-            .map(|token_id| self.nft_token(token_id.clone()).unwrap())
+            //we'll map the ticket IDs into Json Tickets
+            .map(|ticket_id| self.nft_token(U64(ticket_id)).unwrap())
             //since we turned the keys into an iterator, we need to turn it back into a vector to return
             .collect()
     }
 
-    //get the total supply of NFTs for a given owner
+    //get the total supply of tickets for a given owner
     pub fn nft_supply_for_owner(&self, account_id: AccountId) -> U128 {
-        //get the set of tokens for the passed in owner
-        let tokens_for_owner_set = self.tokens_per_owner.get(&account_id);
+        //get the set of tickets for the passed in owner
+        let tickets_for_owner_set = self.tickets_per_owner.get(&account_id);
 
-        //if there is some set of tokens, we'll return the length as a U128
-        if let Some(tokens_for_owner_set) = tokens_for_owner_set {
-            U128(tokens_for_owner_set.len() as u128)
+        //if there is some set of tickets, we'll return the length as a U128
+        if let Some(tickets_for_owner_set) = tickets_for_owner_set {
+            U128(tickets_for_owner_set.len() as u128)
         } else {
-            //if there isn't a set of tokens for the passed in account ID, we'll return 0
+            //if there isn't a set of tickets for the passed in account ID, we'll return 0
             U128(0)
         }
     }
 
-    //Query for all the tokens for an owner
+    //Query for all the tickets for an owner
     pub fn nft_tokens_for_owner(
         &self,
         account_id: AccountId,
         from_index: Option<U128>,
         limit: Option<u64>,
-    ) -> Vec<JsonToken> {
-        //get the set of tokens for the passed in owner
-        let tokens_for_owner_set = self.tokens_per_owner.get(&account_id);
-        //if there is some set of tokens, we'll set the tokens variable equal to that set
-        let tokens = if let Some(tokens_for_owner_set) = tokens_for_owner_set {
-            tokens_for_owner_set
+    ) -> Vec<JsonTicket> {
+        //get the set of tickets for the passed in owner
+        let tickets_for_owner_set = self.tickets_per_owner.get(&account_id);
+        //if there is some set of tickets, we'll set the tickets variable equal to that set
+        let tickets = if let Some(tickets_for_owner_set) = tickets_for_owner_set {
+            tickets_for_owner_set
         } else {
-            //if there is no set of tokens, we'll simply return an empty vector.
+            //if there is no set of tickets, we'll simply return an empty vector.
             return vec![];
         };
 
@@ -76,107 +106,112 @@ impl Contract {
         let start = u128::from(from_index.unwrap_or(U128(0)));
 
         //iterate through the keys vector
-        tokens
+        tickets
             .iter()
             //skip to the index we specified in the start variable
             .skip(start as usize)
             //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
             .take(limit.unwrap_or(50) as usize)
-            //we'll map the token IDs which are strings into Json Tokens
-            .map(|token_id| self.nft_token(token_id.clone()).unwrap())
+            //we'll map the ticket IDs into Json Tickets
+            .map(|ticket_id| self.nft_token(U64(ticket_id)).unwrap())
             //since we turned the keys into an iterator, we need to turn it back into a vector to return
             .collect()
     }
 
-    // Get the total supply of NFTs for a given owner
-    pub fn get_supply_series(&self) -> u64 {
-        self.series_by_id.len()
+    // Get the total number of raffles on the contract
+    pub fn get_supply_raffles(&self) -> u64 {
+        self.raffle_by_id.len()
     }
 
-    // Paginate through all the series on the contract and return the metadata for each one
-    pub fn get_series(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonSeries> {
+    // Paginate through all the raffles on the contract and return the info for each one
+    pub fn get_raffles(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonRaffle> {
         //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
         let start = u128::from(from_index.unwrap_or(U128(0)));
 
-        //iterate through each series using an iterator
-        self.series_by_id
+        //iterate through each raffle using an iterator
+        self.raffle_by_id
             .keys()
             //skip to the index we specified in the start variable
             .skip(start as usize)
             //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
             .take(limit.unwrap_or(50) as usize)
-            //we'll map the series IDs which are strings into Json Series
-            .map(|series_id| self.get_series_info(None, Some(series_id.clone())).unwrap())
+            //we'll map the raffle IDs into Json Raffles
+            .map(|raffle_id| self.get_raffle_info(U64(raffle_id)).unwrap())
             //since we turned the keys into an iterator, we need to turn it back into a vector to return
             .collect()
     }
 
-    // get info for a specific series
-    pub fn get_series_info(
-        &self,
-        mint_id: Option<u64>,
-        series_id: Option<u64>,
-    ) -> Option<JsonSeries> {
-        // If a series ID was passed in, use that. Otherwise, get the series ID from the mint ID.
-        let actual_id = series_id.unwrap_or_else(|| {
-            self.series_id_by_mint_id
-                .get(&mint_id.expect("No mint ID or series ID passed in"))
-                .expect("No series ID found for mint ID")
-        });
-
-        // If there was some series, return the series info
-        if let Some(series) = self.series_by_id.get(&actual_id) {
-            Some(JsonSeries {
-                series_id: actual_id,
-                mint_id: series.mint_id,
-                metadata: series.metadata,
-                royalty: series.royalty,
-                owner_id: series.owner_id,
+    // get info for a specific raffle
+    pub fn get_raffle_info(&self, raffle_id: U64) -> Option<JsonRaffle> {
+        //if there was some raffle, return the raffle info
+        if let Some(raffle) = self.raffle_by_id.get(&raffle_id.0) {
+            let remaining_tickets = remaining_tickets(&raffle).map(U64);
+            Some(JsonRaffle {
+                raffle_id,
+                status: raffle_status(&raffle),
+                keypom_drops: raffle.keypom_drops,
+                metadata: raffle.metadata,
+                remaining_tickets,
+                royalty: raffle.royalty,
+                transferable: raffle.transferable,
+                starts_at: raffle.starts_at.map(U64),
+                ends_at: raffle.ends_at.map(U64),
+                num_winners: raffle.num_winners.map(U64),
+                claim_deadline: raffle.claim_deadline.map(U64),
+                prize_tiers: raffle.prize_tiers,
+                near_prize_pool: U128(raffle.near_prize_pool),
+                prize_split: raffle.prize_split,
+                ticket_price: raffle.ticket_price.map(U128),
+                ft_ticket_price: raffle.ft_ticket_price,
+                beneficiary_id: raffle.beneficiary_id,
+                charity_split: raffle.charity_split,
+                near_proceeds: U128(raffle.near_proceeds),
+                ft_proceeds: raffle
+                    .ft_proceeds
+                    .into_iter()
+                    .map(|(token_id, amount)| (token_id, U128(amount)))
+                    .collect(),
+                unpaid_proceeds: raffle.unpaid_proceeds,
+                owner_id: raffle.owner_id,
             })
         } else {
-            //if there isn't a series, we'll return None
+            //if there isn't a raffle, we'll return None
             None
         }
     }
 
-    //get the total supply of NFTs for a given owner
-    pub fn nft_supply_for_series(&self, mint_id: Option<u64>, series_id: Option<u64>) -> U128 {
-        // If a series ID was passed in, use that. Otherwise, get the series ID from the mint ID.
-        let actual_id = series_id.unwrap_or_else(|| {
-            self.series_id_by_mint_id
-                .get(&mint_id.expect("No mint ID or series ID passed in"))
-                .expect("No series ID found for mint ID")
-        });
-
-        let series = self.series_by_id.get(&actual_id);
+    //get the total supply of tickets for a given raffle
+    pub fn nft_supply_for_raffle(&self, raffle_id: U64) -> U128 {
+        let raffle = self.raffle_by_id.get(&raffle_id.0);
 
-        //if there is some series, get the length of the tokens. Otherwise return -
-        if let Some(series) = series {
-            U128(series.tokens.len() as u128)
+        //if there is some raffle, get the length of the tickets. Otherwise return 0
+        if let Some(raffle) = raffle {
+            U128(raffle.tickets.len() as u128)
         } else {
             U128(0)
         }
     }
 
-    //Query for all the tokens for an owner
-    pub fn nft_tokens_for_series(
+    //get the number of tickets an account holds in a raffle
+    pub fn nft_supply_for_raffle_owner(&self, raffle_id: U64, account_id: AccountId) -> U128 {
+        U128(
+            self.tickets_per_raffle_owner
+                .get(&(raffle_id.0, account_id))
+                .unwrap_or(0) as u128,
+        )
+    }
+
+    //Query for all the tickets in a raffle
+    pub fn nft_tickets_for_raffle(
         &self,
-        mint_id: Option<u64>,
-        series_id: Option<u64>,
+        raffle_id: U64,
         from_index: Option<U128>,
         limit: Option<u64>,
-    ) -> Vec<JsonToken> {
-        // If a series ID was passed in, use that. Otherwise, get the series ID from the mint ID.
-        let actual_id = series_id.unwrap_or_else(|| {
-            self.series_id_by_mint_id
-                .get(&mint_id.expect("No mint ID or series ID passed in"))
-                .expect("No series ID found for mint ID")
-        });
-
-        let series = self.series_by_id.get(&actual_id);
-        
-        let tokens = if let Some(series) = series {
-            series.tokens
+    ) -> Vec<JsonTicket> {
+        let raffle = self.raffle_by_id.get(&raffle_id.0);
+
+        let tickets = if let Some(raffle) = raffle {
+            raffle.tickets
         } else {
             return vec![];
         };
@@ -185,15 +220,136 @@ impl Contract {
         let start = u128::from(from_index.unwrap_or(U128(0)));
 
         //iterate through the keys vector
-        tokens
+        tickets
             .iter()
             //skip to the index we specified in the start variable
             .skip(start as usize)
             //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
             .take(limit.unwrap_or(50) as usize)
-            //we'll map the token IDs which are strings into Json Tokens
-            .map(|token_id| self.nft_token(token_id.clone()).unwrap())
+            //we'll map the ticket IDs into Json Tickets
+            .map(|ticket_id| self.nft_token(U64(ticket_id)).unwrap())
             //since we turned the keys into an iterator, we need to turn it back into a vector to return
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn ticket_ids(tickets: Vec<JsonTicket>) -> Vec<u64> {
+        tickets
+            .into_iter()
+            .map(|ticket| ticket.ticket_id.0)
+            .collect()
+    }
+
+    #[test]
+    fn nft_tokens_paginates_through_all_tickets() {
+        let mut contract = setup_open_raffle();
+        mint(&mut contract, &user(0), 3);
+        mint(&mut contract, &user(1), 2);
+
+        assert_eq!(
+            ticket_ids(contract.nft_tokens(None, None)),
+            vec![0, 1, 2, 3, 4]
+        );
+        assert_eq!(
+            ticket_ids(contract.nft_tokens(Some(U128(1)), Some(2))),
+            vec![1, 2]
+        );
+        assert_eq!(
+            ticket_ids(contract.nft_tokens(Some(U128(4)), Some(2))),
+            vec![4]
+        );
+        assert!(contract.nft_tokens(Some(U128(5)), None).is_empty());
+    }
+
+    #[test]
+    fn nft_tokens_for_owner_paginates_through_the_owners_tickets() {
+        let mut contract = setup_open_raffle();
+        mint(&mut contract, &user(0), 2);
+        mint(&mut contract, &user(1), 3);
+
+        assert_eq!(
+            ticket_ids(contract.nft_tokens_for_owner(user(1), None, None)),
+            vec![2, 3, 4]
+        );
+        assert_eq!(
+            ticket_ids(contract.nft_tokens_for_owner(user(1), Some(U128(1)), Some(1))),
+            vec![3]
+        );
+        assert!(contract
+            .nft_tokens_for_owner(user(2), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn nft_tickets_for_raffle_paginates_through_the_raffles_tickets() {
+        let mut contract = setup_open_raffle();
+        mint(&mut contract, &user(0), 4);
+
+        assert_eq!(
+            ticket_ids(contract.nft_tickets_for_raffle(U64(RAFFLE_ID), Some(U128(2)), Some(5))),
+            vec![2, 3]
+        );
+        assert!(contract
+            .nft_tickets_for_raffle(U64(RAFFLE_ID + 1), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn get_raffles_paginates_through_all_raffles() {
+        let mut contract = setup_draft_raffle(raffle_metadata(None));
+        for raffle_id in [RAFFLE_ID + 1, RAFFLE_ID + 2] {
+            set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+            contract.create_raffle(
+                raffle_id,
+                raffle_metadata(None),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            );
+        }
+
+        let raffle_ids = |raffles: Vec<JsonRaffle>| -> Vec<u64> {
+            raffles
+                .into_iter()
+                .map(|raffle| raffle.raffle_id.0)
+                .collect()
+        };
+        assert_eq!(
+            raffle_ids(contract.get_raffles(None, None)),
+            vec![RAFFLE_ID, RAFFLE_ID + 1, RAFFLE_ID + 2]
+        );
+        assert_eq!(
+            raffle_ids(contract.get_raffles(Some(U128(1)), Some(1))),
+            vec![RAFFLE_ID + 1]
+        );
+        assert!(contract.get_raffles(Some(U128(3)), None).is_empty());
+    }
+
+    #[test]
+    fn raffle_info_reports_the_remaining_tickets() {
+        let mut contract = setup_draft_raffle(raffle_metadata(Some(5)));
+        set_caller(&raffle_owner(), 0);
+        contract.open_raffle(U64(RAFFLE_ID));
+        mint(&mut contract, &user(0), 2);
+
+        let info = contract.get_raffle_info(U64(RAFFLE_ID)).unwrap();
+        assert_eq!(info.remaining_tickets, Some(U64(3)));
+    }
+
+    #[test]
+    fn uncapped_raffles_report_no_remaining_tickets() {
+        let contract = setup_open_raffle();
+        let info = contract.get_raffle_info(U64(RAFFLE_ID)).unwrap();
+        assert_eq!(info.remaining_tickets, None);
+        assert!(contract.get_raffle_info(U64(RAFFLE_ID + 1)).is_none());
+    }
+}
@@ -3,7 +3,8 @@ use std::fmt;
 use near_sdk::serde::{Deserialize, Serialize};
 
 /// Enum that represents the data type of the EventLog.
-/// The enum can either be an NftMint or an NftTransfer.
+/// The enum can either be an NftMint, an NftTransfer, an NftBurn, a RaffleDraw, a DonationReceipt
+/// or a DonationRefund.
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "event", content = "data")]
 #[serde(rename_all = "snake_case")]
@@ -12,6 +13,10 @@ use near_sdk::serde::{Deserialize, Serialize};
 pub enum EventLogVariant {
     NftMint(Vec<NftMintLog>),
     NftTransfer(Vec<NftTransferLog>),
+    NftBurn(Vec<NftBurnLog>),
+    RaffleDraw(Vec<RaffleDrawLog>),
+    DonationReceipt(Vec<DonationReceiptLog>),
+    DonationRefund(Vec<DonationReceiptLog>),
 }
 
 /// Interface to capture data about an event
@@ -78,6 +83,65 @@ pub struct NftTransferLog {
     pub memo: Option<String>,
 }
 
+/// An event log to capture token burning
+///
+/// Arguments
+/// * `owner_id`: owner of the tokens to burn
+/// * `authorized_id`: approved account to burn, if applicable
+/// * `token_ids`: ["1", "abc"]
+/// * `memo`: optional message
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftBurnLog {
+    pub owner_id: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorized_id: Option<String>,
+
+    pub token_ids: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// An event log to capture a winning ticket being drawn
+///
+/// Arguments
+/// * `raffle_id`: "1"
+/// * `owner_id`: "winner.near"
+/// * `token_id`: "12"
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RaffleDrawLog {
+    pub raffle_id: String,
+    pub owner_id: String,
+    pub token_id: String,
+}
+
+/// An event log to capture the share of a ticket purchase that went to a recipient.
+/// Refunds of a ticket purchase use the same log with the share the recipient gave back.
+///
+/// Arguments
+/// * `raffle_id`: "1"
+/// * `donor_id`: "buyer.near"
+/// * `recipient_id`: "charity.near"
+/// * `amount`: "1000000000000000000000000"
+/// * `token_id`: optional token contract, omitted for NEAR
+/// * `timestamp`: block timestamp in nanoseconds
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DonationReceiptLog {
+    pub raffle_id: String,
+    pub donor_id: String,
+    pub recipient_id: String,
+    pub amount: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_id: Option<String>,
+
+    pub timestamp: String,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -135,4 +199,88 @@ mod tests {
         };
         assert_eq!(expected, log.to_string());
     }
+
+    #[test]
+    fn nep_format_burn() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_burn","data":[{"owner_id":"foundation.near","token_ids":["aurora","proximitylabs"]}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::NftBurn(vec![NftBurnLog {
+                owner_id: "foundation.near".to_owned(),
+                authorized_id: None,
+                token_ids: vec!["aurora".to_string(), "proximitylabs".to_string()],
+                memo: None,
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_raffle_draw() {
+        let expected = r#"EVENT_JSON:{"standard":"aha_raffle","version":"1.0.0","event":"raffle_draw","data":[{"raffle_id":"1","owner_id":"user1.near","token_id":"4"},{"raffle_id":"1","owner_id":"user2.near","token_id":"0"}]}"#;
+        let log = EventLog {
+            standard: "aha_raffle".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::RaffleDraw(vec![
+                RaffleDrawLog {
+                    raffle_id: "1".to_string(),
+                    owner_id: "user1.near".to_string(),
+                    token_id: "4".to_string(),
+                },
+                RaffleDrawLog {
+                    raffle_id: "1".to_string(),
+                    owner_id: "user2.near".to_string(),
+                    token_id: "0".to_string(),
+                },
+            ]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_donation_receipt() {
+        let expected = r#"EVENT_JSON:{"standard":"aha_raffle","version":"1.0.0","event":"donation_receipt","data":[{"raffle_id":"1","donor_id":"user1.near","recipient_id":"charity.near","amount":"700","token_id":"usdc.near","timestamp":"1700000000000000000"},{"raffle_id":"1","donor_id":"user1.near","recipient_id":"owner.near","amount":"300","timestamp":"1700000000000000000"}]}"#;
+        let log = EventLog {
+            standard: "aha_raffle".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::DonationReceipt(vec![
+                DonationReceiptLog {
+                    raffle_id: "1".to_string(),
+                    donor_id: "user1.near".to_string(),
+                    recipient_id: "charity.near".to_string(),
+                    amount: "700".to_string(),
+                    token_id: Some("usdc.near".to_string()),
+                    timestamp: "1700000000000000000".to_string(),
+                },
+                DonationReceiptLog {
+                    raffle_id: "1".to_string(),
+                    donor_id: "user1.near".to_string(),
+                    recipient_id: "owner.near".to_string(),
+                    amount: "300".to_string(),
+                    token_id: None,
+                    timestamp: "1700000000000000000".to_string(),
+                },
+            ]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_donation_refund() {
+        let expected = r#"EVENT_JSON:{"standard":"aha_raffle","version":"1.0.0","event":"donation_refund","data":[{"raffle_id":"1","donor_id":"user1.near","recipient_id":"owner.near","amount":"300","timestamp":"1700000000000000000"}]}"#;
+        let log = EventLog {
+            standard: "aha_raffle".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::DonationRefund(vec![DonationReceiptLog {
+                raffle_id: "1".to_string(),
+                donor_id: "user1.near".to_string(),
+                recipient_id: "owner.near".to_string(),
+                amount: "300".to_string(),
+                token_id: None,
+                timestamp: "1700000000000000000".to_string(),
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
 }
@@ -1,4 +1,5 @@
 use crate::*;
+use near_sdk::json_types::{Base64VecU8, U64};
 use near_sdk::CryptoHash;
 use std::mem::size_of;
 
@@ -45,6 +46,511 @@ pub(crate) fn hash_account_id(account_id: &String) -> CryptoHash {
     hash
 }
 
+// Derive a pseudo-random u64 from the passed-in seed and nonce.
+pub(crate) fn random_u64(seed: &[u8], nonce: u64) -> u64 {
+    // Hash the seed together with the nonce so that every nonce gives a different number.
+    let mut bytes = seed.to_vec();
+    bytes.extend_from_slice(&nonce.to_le_bytes());
+    let hash = env::sha256(&bytes);
+    // Take the first 8 bytes of the hash as the random number.
+    let mut number = [0u8; 8];
+    number.copy_from_slice(&hash[..8]);
+    u64::from_le_bytes(number)
+}
+
+// Get the lifecycle state of a raffle, taking its start and end times into account.
+pub(crate) fn raffle_status(raffle: &Raffle) -> RaffleStatus {
+    // Raffles that were closed manually or already drawn aren't affected by the schedule.
+    if raffle.status != RaffleStatus::Draft && raffle.status != RaffleStatus::Open {
+        return raffle.status;
+    }
+
+    let now = env::block_timestamp();
+    if raffle.ends_at.is_some_and(|ends_at| now >= ends_at) {
+        return RaffleStatus::Closed;
+    }
+
+    match raffle.starts_at {
+        // The raffle hasn't started yet, so it can't be open.
+        Some(starts_at) if now < starts_at => RaffleStatus::Draft,
+        // The start time has passed, so the raffle is open even if nobody opened it manually.
+        Some(_) => RaffleStatus::Open,
+        None => raffle.status,
+    }
+}
+
+// Tickets can't change hands from the moment a raffle closes until it's settled, so the owners
+// recorded at the draw are the owners who can claim.
+pub(crate) fn tickets_locked(raffle: &Raffle) -> bool {
+    matches!(
+        raffle_status(raffle),
+        RaffleStatus::Closed | RaffleStatus::Drawn
+    )
+}
+
+// How many more tickets can be minted for a raffle, if it has a cap.
+pub(crate) fn remaining_tickets(raffle: &Raffle) -> Option<u64> {
+    raffle
+        .metadata
+        .max_tickets
+        .map(|max| max.saturating_sub(raffle.tickets.len()))
+}
+
+// Get the seed a draw round uses: the block's seed, mixed with the revealed secret if the
+// round used commit-reveal.
+pub(crate) fn draw_seed(block_seed: &[u8], secret: Option<&[u8]>) -> Vec<u8> {
+    match secret {
+        Some(secret) => env::sha256(&[block_seed, secret].concat()),
+        None => block_seed.to_vec(),
+    }
+}
+
+// Pick `num_winners` distinct indices out of `total` using the passed-in seed, never picking
+// any of the `excluded` indices. This is deterministic, so anyone with the seed and the excluded
+// indices can re-derive the same result.
+pub(crate) fn draw_indices(
+    seed: &[u8],
+    total: u64,
+    num_winners: u64,
+    excluded: &[u64],
+) -> Vec<u64> {
+    // Partial Fisher-Yates shuffle over the indices. Only the positions that were swapped
+    // are kept in memory so we never need to load the full list.
+    let mut value_at: HashMap<u64, u64> = HashMap::new();
+    let mut position_of: HashMap<u64, u64> = HashMap::new();
+
+    // Swap the values at positions i and j, returning the value that ends up at i.
+    fn swap(
+        value_at: &mut HashMap<u64, u64>,
+        position_of: &mut HashMap<u64, u64>,
+        i: u64,
+        j: u64,
+    ) -> u64 {
+        let value_i = *value_at.get(&i).unwrap_or(&i);
+        let value_j = *value_at.get(&j).unwrap_or(&j);
+        value_at.insert(i, value_j);
+        value_at.insert(j, value_i);
+        position_of.insert(value_j, i);
+        position_of.insert(value_i, j);
+        value_j
+    }
+
+    // Move the excluded indices to the front of the list so they can't be picked.
+    let offset = excluded.len() as u64;
+    for (i, index) in excluded.iter().enumerate() {
+        let position = *position_of.get(index).unwrap_or(index);
+        swap(&mut value_at, &mut position_of, i as u64, position);
+    }
+
+    let mut indices = vec![];
+    for n in 0..num_winners {
+        // Pick a random position in the part of the list that hasn't been drawn yet.
+        let i = offset + n;
+        let j = i + random_u64(seed, n) % (total - i);
+        indices.push(swap(&mut value_at, &mut position_of, i, j));
+    }
+
+    indices
+}
+
+// Make sure a winning ticket's prize can still be claimed and mark it as claimed.
+// Returns the ticket's position in the raffle's winners.
+pub(crate) fn claim_winner(raffle: &mut Raffle, ticket_id: U64) -> u64 {
+    require!(
+        raffle_status(raffle) == RaffleStatus::Drawn,
+        "Prizes can only be claimed while the raffle is drawn"
+    );
+    let claim_deadline = raffle.claim_deadline;
+    let (winner_index, winner) = raffle
+        .winners
+        .iter_mut()
+        .enumerate()
+        .find(|(_, winner)| winner.ticket_id == ticket_id)
+        .expect("Ticket did not win");
+
+    if !winner.claimed {
+        require!(
+            claim_deadline.is_none_or(|deadline| env::block_timestamp() < deadline),
+            "Claim deadline has passed"
+        );
+        winner.claimed = true;
+    }
+
+    winner_index as u64
+}
+
+// Get the prize tier index the n-th winner falls into, along with their position within that tier.
+pub(crate) fn prize_tier_for_winner(raffle: &Raffle, winner_index: u64) -> Option<(u64, u64)> {
+    let mut first_in_tier = 0;
+    for (tier_index, tier) in raffle.prize_tiers.iter().enumerate() {
+        if winner_index < first_in_tier + tier.quantity {
+            return Some((tier_index as u64, winner_index - first_in_tier));
+        }
+        first_in_tier += tier.quantity;
+    }
+
+    None
+}
+
+// Get the escrowed asset the n-th winner receives from their prize tier, along with their
+// position within the tier.
+pub(crate) fn prize_asset_for_winner(
+    raffle: &Raffle,
+    winner_index: u64,
+) -> Option<(&PrizeAsset, u64)> {
+    let (tier_index, position) = prize_tier_for_winner(raffle, winner_index)?;
+    raffle.prize_tiers[tier_index as usize]
+        .asset
+        .as_ref()
+        .map(|asset| (asset, position))
+}
+
+// Make sure every asset referenced by the raffle's prize tiers is held in escrow.
+pub(crate) fn assert_prize_tiers_escrowed(raffle: &Raffle) {
+    let mut near_needed: Balance = 0;
+    let mut ft_needed: HashMap<&AccountId, Balance> = HashMap::new();
+
+    for tier in raffle.prize_tiers.iter() {
+        match tier.asset.as_ref() {
+            Some(PrizeAsset::Nft {
+                nft_contract_id,
+                token_ids,
+            }) => {
+                for token_id in token_ids {
+                    require!(
+                        raffle.nft_prizes.iter().any(|prize| {
+                            &prize.nft_contract_id == nft_contract_id && &prize.token_id == token_id
+                        }),
+                        &format!("NFT {} from {} is not in escrow", token_id, nft_contract_id)
+                    );
+                }
+            }
+            Some(PrizeAsset::Ft { token_id, amount }) => {
+                *ft_needed.entry(token_id).or_insert(0) += amount.0 * tier.quantity as u128;
+            }
+            Some(PrizeAsset::Near { amount }) => {
+                near_needed += amount.0 * tier.quantity as u128;
+            }
+            None => {}
+        }
+    }
+
+    require!(
+        near_needed <= raffle.near_prize_pool,
+        "NEAR prize pool is too small for the prize tiers"
+    );
+    for (token_id, needed) in ft_needed {
+        let pool_amount = raffle
+            .ft_prize_pools
+            .get(token_id)
+            .map_or(0, |pool| pool.amount.0);
+        require!(
+            needed <= pool_amount,
+            &format!("{} prize pool is too small for the prize tiers", token_id)
+        );
+    }
+}
+
+// Split a raffle's NEAR prize pool between `num_winners` winners. If the raffle has prize tiers,
+// each winner gets the NEAR amount of their tier. If it has a basis-point schedule, each winner
+// gets their entry of it. Otherwise the pool is split evenly.
+pub(crate) fn near_prize_payouts(raffle: &Raffle, num_winners: u64) -> Vec<U128> {
+    if !raffle.prize_tiers.is_empty() {
+        return (0..num_winners)
+            .map(|index| match prize_asset_for_winner(raffle, index) {
+                Some((PrizeAsset::Near { amount }, _)) => *amount,
+                _ => U128(0),
+            })
+            .collect();
+    }
+
+    if raffle.prize_split.is_empty() {
+        let share = raffle.near_prize_pool / num_winners as u128;
+        return (0..num_winners).map(|_| U128(share)).collect();
+    }
+
+    raffle
+        .prize_split
+        .iter()
+        .map(|basis_points| royalty_to_payout(*basis_points, raffle.near_prize_pool))
+        .collect()
+}
+
+// Get the amount of a fungible token the n-th winner can claim. If any prize tier pays out this
+// token, each winner gets the amount of their tier. Otherwise the pool is split evenly.
+pub(crate) fn ft_prize_share(raffle: &Raffle, winner_index: u64, token_id: &AccountId) -> Balance {
+    // Get the token and amount a prize asset pays out, if it is a fungible token prize.
+    let ft_asset = |asset: Option<&PrizeAsset>| match asset {
+        Some(PrizeAsset::Ft { token_id, amount }) => Some((token_id.clone(), amount.0)),
+        _ => None,
+    };
+
+    let has_tier_for_token = raffle.prize_tiers.iter().any(|tier| {
+        ft_asset(tier.asset.as_ref()).is_some_and(|(tier_token_id, _)| &tier_token_id == token_id)
+    });
+    if has_tier_for_token {
+        return prize_asset_for_winner(raffle, winner_index)
+            .and_then(|(asset, _)| ft_asset(Some(asset)))
+            .filter(|(tier_token_id, _)| tier_token_id == token_id)
+            .map_or(0, |(_, amount)| amount);
+    }
+
+    raffle
+        .ft_prize_pools
+        .get(token_id)
+        .map_or(0, |pool| pool.amount.0 / raffle.winners.len() as u128)
+}
+
+// Check whether every prize the n-th winner won has been sent to them.
+pub(crate) fn winner_prizes_claimed(raffle: &Raffle, winner_index: u64) -> bool {
+    let winner = &raffle.winners[winner_index as usize];
+    let near_claimed = winner.near_prize.0 == 0 || winner.near_prize_claimed;
+    let nft_claimed =
+        nft_prize_index(raffle, winner_index).is_none_or(|index| raffle.nft_prizes[index].claimed);
+    let ft_claimed = raffle.ft_prize_pools.iter().all(|(token_id, pool)| {
+        ft_prize_share(raffle, winner_index, token_id) == 0
+            || pool.claimed.contains(&winner.ticket_id)
+    });
+
+    near_claimed && nft_claimed && ft_claimed
+}
+
+// Get how much of the raffle's NEAR prize pool hasn't been sent to a winner.
+pub(crate) fn unclaimed_near_prize(raffle: &Raffle) -> Balance {
+    let claimed: Balance = raffle
+        .winners
+        .iter()
+        .filter(|winner| winner.near_prize_claimed)
+        .map(|winner| winner.near_prize.0)
+        .sum();
+    raffle.near_prize_pool.saturating_sub(claimed)
+}
+
+// Get how much of a fungible token prize pool hasn't been sent to a winner.
+pub(crate) fn unclaimed_ft_prize(raffle: &Raffle, token_id: &AccountId) -> Balance {
+    let Some(pool) = raffle.ft_prize_pools.get(token_id) else {
+        return 0;
+    };
+    let claimed: Balance = raffle
+        .winners
+        .iter()
+        .enumerate()
+        .filter(|(_, winner)| pool.claimed.contains(&winner.ticket_id))
+        .map(|(winner_index, _)| ft_prize_share(raffle, winner_index as u64, token_id))
+        .sum();
+    pool.amount.0.saturating_sub(claimed)
+}
+
+// Compute a * b / c, rounding down, even when a * b doesn't fit in a u128.
+// The result has to fit in a u128, which is always the case when a <= c.
+pub(crate) fn mul_div(a: u128, b: u128, c: u128) -> u128 {
+    if let Some(product) = a.checked_mul(b) {
+        return product / c;
+    }
+
+    // Multiply into a 256-bit (high, low) pair, 64 bits at a time
+    let mask = u128::from(u64::MAX);
+    let (a_high, a_low) = (a >> 64, a & mask);
+    let (b_high, b_low) = (b >> 64, b & mask);
+    let low_low = a_low * b_low;
+    let high_low = a_high * b_low;
+    let low_high = a_low * b_high;
+    let middle = (low_low >> 64) + (high_low & mask) + (low_high & mask);
+    let low = (middle << 64) | (low_low & mask);
+    let high = a_high * b_high + (high_low >> 64) + (low_high >> 64) + (middle >> 64);
+
+    // Long division of the 256-bit product by c, one bit at a time
+    let mut quotient: u128 = 0;
+    let mut remainder: u128 = 0;
+    for bit in (0..256).rev() {
+        let next_bit = if bit >= 128 {
+            (high >> (bit - 128)) & 1
+        } else {
+            (low >> bit) & 1
+        };
+        let carry = remainder >> 127 == 1;
+        remainder = (remainder << 1) | next_bit;
+        quotient <<= 1;
+        if carry || remainder >= c {
+            remainder = remainder.wrapping_sub(c);
+            quotient |= 1;
+        }
+    }
+
+    quotient
+}
+
+// Get the index into the raffle's escrowed NFTs of the prize for the n-th winner. If the winner's
+// prize tier hands out NFTs, this is the NFT for their position in the tier. Without prize tiers,
+// the n-th escrowed NFT goes to the n-th winner.
+pub(crate) fn nft_prize_index(raffle: &Raffle, winner_index: u64) -> Option<usize> {
+    if raffle.prize_tiers.is_empty() {
+        return (winner_index < raffle.nft_prizes.len() as u64).then_some(winner_index as usize);
+    }
+
+    match prize_asset_for_winner(raffle, winner_index) {
+        Some((
+            PrizeAsset::Nft {
+                nft_contract_id,
+                token_ids,
+            },
+            position,
+        )) => {
+            let token_id = token_ids.get(position as usize)?;
+            raffle.nft_prizes.iter().position(|prize| {
+                &prize.nft_contract_id == nft_contract_id && &prize.token_id == token_id
+            })
+        }
+        _ => None,
+    }
+}
+
+// Log the winning tickets of a draw round, with one entry per ticket in the order they were drawn.
+pub(crate) fn log_raffle_draw(raffle_id: RaffleId, winners: &[Winner]) {
+    // Construct the draw log as per the events standard.
+    let raffle_draw_log: EventLog = EventLog {
+        standard: RAFFLE_STANDARD_NAME.to_string(),
+        version: RAFFLE_SPEC.to_string(),
+        event: EventLogVariant::RaffleDraw(
+            winners
+                .iter()
+                .map(|winner| RaffleDrawLog {
+                    raffle_id: raffle_id.to_string(),
+                    owner_id: winner.owner_id.to_string(),
+                    token_id: winner.ticket_id.0.to_string(),
+                })
+                .collect(),
+        ),
+    };
+
+    // Log the serialized JSON.
+    env::log_str(&raffle_draw_log.to_string());
+}
+
+// Split ticket sale proceeds between the raffle's charities and its beneficiary.
+// Charities get their share in basis points and the beneficiary receives whatever is left.
+pub(crate) fn proceeds_payouts(raffle: &Raffle, amount: Balance) -> Vec<(AccountId, Balance)> {
+    let mut charities: Vec<(&AccountId, &u32)> = raffle.charity_split.iter().collect();
+    charities.sort();
+
+    let mut payouts = vec![];
+    let mut remaining = amount;
+    for (charity_id, bps) in charities {
+        let share = amount * Balance::from(*bps) / 10_000;
+        remaining -= share;
+        payouts.push((charity_id.clone(), share));
+    }
+    let beneficiary_id = raffle
+        .beneficiary_id
+        .clone()
+        .unwrap_or_else(|| raffle.owner_id.clone());
+    payouts.push((beneficiary_id, remaining));
+
+    payouts.retain(|(_, share)| *share > 0);
+    payouts
+}
+
+// Build one donation log entry for each share of a ticket purchase.
+fn donation_logs(
+    raffle_id: RaffleId,
+    donor_id: &AccountId,
+    token_id: Option<&AccountId>,
+    payouts: &[(AccountId, Balance)],
+) -> Vec<DonationReceiptLog> {
+    let timestamp = env::block_timestamp().to_string();
+    payouts
+        .iter()
+        .map(|(recipient_id, amount)| DonationReceiptLog {
+            raffle_id: raffle_id.to_string(),
+            donor_id: donor_id.to_string(),
+            recipient_id: recipient_id.to_string(),
+            amount: amount.to_string(),
+            token_id: token_id.map(|token_id| token_id.to_string()),
+            timestamp: timestamp.clone(),
+        })
+        .collect()
+}
+
+// Log a donation receipt for each share of a ticket purchase.
+pub(crate) fn log_donation_receipt(
+    raffle_id: RaffleId,
+    donor_id: &AccountId,
+    token_id: Option<&AccountId>,
+    payouts: &[(AccountId, Balance)],
+) {
+    // Construct the receipt log as per the events standard.
+    let donation_receipt_log: EventLog = EventLog {
+        standard: RAFFLE_STANDARD_NAME.to_string(),
+        version: RAFFLE_SPEC.to_string(),
+        event: EventLogVariant::DonationReceipt(donation_logs(
+            raffle_id, donor_id, token_id, payouts,
+        )),
+    };
+
+    // Log the serialized JSON.
+    env::log_str(&donation_receipt_log.to_string());
+}
+
+// Log the shares of a refunded ticket purchase that the recipients no longer receive.
+pub(crate) fn log_donation_refund(
+    raffle_id: RaffleId,
+    donor_id: &AccountId,
+    token_id: Option<&AccountId>,
+    payouts: &[(AccountId, Balance)],
+) {
+    // Construct the refund log as per the events standard.
+    let donation_refund_log: EventLog = EventLog {
+        standard: RAFFLE_STANDARD_NAME.to_string(),
+        version: RAFFLE_SPEC.to_string(),
+        event: EventLogVariant::DonationRefund(donation_logs(
+            raffle_id, donor_id, token_id, payouts,
+        )),
+    };
+
+    // Log the serialized JSON.
+    env::log_str(&donation_refund_log.to_string());
+}
+
+// Make sure the split of ticket sale proceeds can still change. Once a ticket has been sold,
+// the split is locked so the receipts already logged match what is paid out.
+pub(crate) fn assert_no_proceeds(raffle: &Raffle) {
+    require!(
+        raffle.near_proceeds == 0 && raffle.ft_proceeds.values().all(|amount| *amount == 0),
+        "Proceeds split can't change once tickets have been sold"
+    );
+}
+
+// Log a single mint event with one entry per owner and the tickets they received.
+pub(crate) fn log_nft_mint(minted: &[(AccountId, Vec<TicketId>)]) {
+    // Construct the mint log as per the events standard.
+    let nft_mint_log: EventLog = EventLog {
+        // Standard name ("nep171")
+        standard: NFT_STANDARD_NAME.to_string(),
+        // Version of the standard ("nft-1.0.0")
+        version: NFT_METADATA_SPEC.to_string(),
+        // The data related with the event stored in a vector
+        event: EventLogVariant::NftMint(
+            minted
+                .iter()
+                .map(|(owner_id, tickets)| NftMintLog {
+                    // Owner of the tickets
+                    owner_id: owner_id.to_string(),
+                    // Vector of ticket IDs that were minted
+                    token_ids: tickets
+                        .iter()
+                        .map(|ticket_id| ticket_id.to_string())
+                        .collect(),
+                    // An optional memo to include
+                    memo: None,
+                })
+                .collect(),
+        ),
+    };
+
+    // Log the serialized JSON
+    env::log_str(&nft_mint_log.to_string());
+}
+
 // Used to make sure the user attached exactly 1 yoctoNEAR.
 pub(crate) fn assert_one_yocto() {
     assert_eq!(
@@ -94,9 +600,204 @@ impl Contract {
         )
     }
 
+    // Make sure the caller is either the owner of the raffle or the contract owner.
+    pub(crate) fn assert_raffle_owner(&self, raffle: &Raffle) {
+        let predecessor = env::predecessor_account_id();
+        assert!(
+            raffle.owner_id == predecessor || self.owner_id == predecessor,
+            "only raffle owner"
+        )
+    }
+
+    // Move a raffle from one lifecycle state to the next. Only the raffle owner can do this.
+    pub(crate) fn internal_update_raffle_status(
+        &mut self,
+        raffle_id: RaffleId,
+        from: RaffleStatus,
+        to: RaffleStatus,
+    ) {
+        let mut raffle = self.raffle_by_id.get(&raffle_id).expect("Not a raffle");
+        self.assert_raffle_owner(&raffle);
+
+        let status = raffle_status(&raffle);
+        require!(
+            status == from,
+            &format!("Raffle must be {:?}, but is {:?}", from, status)
+        );
+        raffle.status = to;
+
+        self.raffle_by_id.insert(&raffle_id, &raffle);
+    }
+
+    // Stop counting a raffle towards its creator's active raffles.
+    pub(crate) fn internal_remove_active_raffle(&mut self, creator_id: &AccountId) {
+        let active_raffles = self.active_raffles_per_creator.get(creator_id).unwrap_or(0);
+        if active_raffles <= 1 {
+            self.active_raffles_per_creator.remove(creator_id);
+        } else {
+            self.active_raffles_per_creator
+                .insert(creator_id, &(active_raffles - 1));
+        }
+    }
+
+    // Run a draw round for the raffle, filling the passed-in positions of its winners list.
+    // Tickets drawn in earlier rounds can't be drawn again. If the owner committed to a secret,
+    // it must be revealed and is mixed into the block's seed.
+    pub(crate) fn internal_draw_round(
+        &self,
+        raffle: &mut Raffle,
+        slots: Vec<u64>,
+        secret: Option<Base64VecU8>,
+    ) -> Vec<Winner> {
+        let block_seed = env::random_seed();
+        let commitment = raffle.draw_commitment.take();
+        let seed = if let Some(commitment) = commitment.as_ref() {
+            let secret = secret
+                .as_ref()
+                .expect("Raffle uses commit-reveal. Must reveal the secret to draw");
+            require!(
+                &env::sha256(&secret.0) == commitment,
+                "Secret does not match the commitment"
+            );
+            draw_seed(&block_seed, Some(&secret.0))
+        } else {
+            draw_seed(&block_seed, None)
+        };
+
+        // Exclude every ticket drawn in an earlier round
+        let excluded: Vec<u64> = raffle
+            .draw_rounds
+            .iter()
+            .flat_map(|round| round.indices.iter().map(|index| index.0))
+            .collect();
+        let tickets = raffle.tickets.as_vector();
+        let num_winners = slots.len() as u64;
+        require!(
+            num_winners <= tickets.len() - excluded.len() as u64,
+            "Not enough tickets left to draw from"
+        );
+        let indices = draw_indices(&seed, tickets.len(), num_winners, &excluded);
+        // Drawn tickets can't win again, so they're no longer live
+        raffle.metadata.live_tickets = raffle.metadata.live_tickets.saturating_sub(num_winners);
+
+        let winners: Vec<Winner> = indices
+            .iter()
+            .map(|index| {
+                // Record the winning ticket along with its current owner.
+                let ticket_id = tickets.get(*index).expect("No ticket");
+                let ticket = self.ticket_by_id.get(&ticket_id).expect("No ticket");
+                Winner {
+                    ticket_id: U64(ticket_id),
+                    owner_id: ticket.owner_id,
+                    prize_tier: None,
+                    near_prize: U128(0),
+                    near_prize_claimed: false,
+                    claimed: false,
+                }
+            })
+            .collect();
+
+        // Keep the full transcript so the round can be verified
+        raffle.draw_rounds.push(DrawTranscript {
+            block_height: U64(env::block_height()),
+            block_seed: Base64VecU8(block_seed),
+            secret: commitment.as_ref().and(secret),
+            commitment: commitment.map(Base64VecU8),
+            seed: Base64VecU8(seed),
+            num_tickets: U64(tickets.len()),
+            num_winners: U64(num_winners),
+            indices: indices.into_iter().map(U64).collect(),
+            ticket_ids: winners.iter().map(|winner| winner.ticket_id).collect(),
+            slots: slots.into_iter().map(U64).collect(),
+        });
+
+        winners
+    }
+
+    // Mint `ticket_amount` new tickets for an open raffle to the receiver. The storage payer, if any,
+    // gets the ticket's storage back when it's burned.
+    // The caller is responsible for writing the raffle back to storage and logging the mint.
+    pub(crate) fn internal_mint_tickets(
+        &mut self,
+        raffle_id: RaffleId,
+        raffle: &mut Raffle,
+        receiver_id: &AccountId,
+        ticket_amount: u64,
+        storage_payer: Option<&AccountId>,
+    ) -> Vec<TicketId> {
+        require!(
+            raffle_status(raffle) == RaffleStatus::Open,
+            "Raffle is not open for minting"
+        );
+
+        // Ensure we don't mint past the raffle's ticket cap
+        if let Some(remaining) = remaining_tickets(raffle) {
+            require!(
+                ticket_amount <= remaining,
+                "Cannot mint any more tickets for the given raffle. Limit reached"
+            );
+        }
+        self.assert_within_account_limit(raffle, raffle_id, receiver_id, ticket_amount);
+
+        let mut tickets = vec![];
+        for _ in 0..ticket_amount {
+            // Ticket IDs are unique across every raffle on the contract
+            let ticket_id = self.next_ticket_id;
+            self.next_ticket_id += 1;
+            tickets.push(ticket_id);
+
+            raffle.tickets.insert(&ticket_id);
+            // Specify the ticket struct that contains the owner ID
+            let ticket = Ticket {
+                // Series ID that the ticket belongs to
+                raffle_id,
+                // Set the owner ID equal to the receiver ID passed into the function
+                owner_id: receiver_id.clone(),
+                // Set the approved account IDs to the default value (an empty map)
+                approved_account_ids: Default::default(),
+                // The next approval ID is set to 0
+                next_approval_id: 0,
+            };
+
+            require!(
+                self.ticket_by_id.insert(&ticket_id, &ticket).is_none(),
+                "Ticket already exists"
+            );
+            if let Some(storage_payer) = storage_payer {
+                self.storage_payer_by_ticket
+                    .insert(&ticket_id, storage_payer);
+            }
+        }
+        self.internal_add_tickets_to_owner(raffle_id, receiver_id, &tickets);
+        raffle.metadata.live_tickets += ticket_amount;
+
+        tickets
+    }
+
+    // Panic if receiving `amount` more tickets would put the account over the raffle's per-account cap.
+    pub(crate) fn assert_within_account_limit(
+        &self,
+        raffle: &Raffle,
+        raffle_id: RaffleId,
+        account_id: &AccountId,
+        amount: u64,
+    ) {
+        if let Some(max) = raffle.metadata.max_tickets_per_account {
+            let held = self
+                .tickets_per_raffle_owner
+                .get(&(raffle_id, account_id.clone()))
+                .unwrap_or(0);
+            require!(
+                held + amount <= max,
+                format!("Account can hold at most {} tickets for this raffle", max)
+            );
+        }
+    }
+
     // Add a set of tickets to the set of tickets an owner has.
     pub(crate) fn internal_add_tickets_to_owner(
         &mut self,
+        raffle_id: RaffleId,
         account_id: &AccountId,
         ticket_id: &Vec<TicketId>,
     ) {
@@ -120,11 +821,18 @@ impl Contract {
 
         // Insert that set for the given account ID.
         self.tickets_per_owner.insert(account_id, &ticket_set);
+
+        // Keep the per-raffle count in sync.
+        let key = (raffle_id, account_id.clone());
+        let count = self.tickets_per_raffle_owner.get(&key).unwrap_or(0);
+        self.tickets_per_raffle_owner
+            .insert(&key, &(count + ticket_id.len() as u64));
     }
 
     // Remove a ticket from an owner (internal method and can't be called directly via CLI).
     pub(crate) fn internal_remove_tickets_from_owner(
         &mut self,
+        raffle_id: RaffleId,
         account_id: &AccountId,
         ticket_id: &Vec<TicketId>,
     ) {
@@ -147,6 +855,19 @@ impl Contract {
             // If the ticket set is not empty, we simply insert it back for the account ID.
             self.tickets_per_owner.insert(account_id, &ticket_set);
         }
+
+        // Keep the per-raffle count in sync, dropping it once the account holds none.
+        let key = (raffle_id, account_id.clone());
+        let count = self
+            .tickets_per_raffle_owner
+            .get(&key)
+            .unwrap_or(0)
+            .saturating_sub(ticket_id.len() as u64);
+        if count == 0 {
+            self.tickets_per_raffle_owner.remove(&key);
+        } else {
+            self.tickets_per_raffle_owner.insert(&key, &count);
+        }
     }
 
     // Transfer the ticket to the receiver_id.
@@ -193,10 +914,30 @@ impl Contract {
             "The ticket owner and the receiver should be different"
         );
 
+        let raffle = self
+            .raffle_by_id
+            .get(&ticket.raffle_id)
+            .expect("Not a raffle");
+        require!(
+            raffle.transferable,
+            "Tickets for this raffle are not transferable"
+        );
+        require!(
+            !tickets_locked(&raffle),
+            "Tickets can't be transferred until the raffle is settled"
+        );
+
+        // Make sure the receiver stays within the raffle's per-account cap.
+        self.assert_within_account_limit(&raffle, ticket.raffle_id, receiver_id, 1);
+
         // Remove the ticket from its current owner's set.
-        self.internal_remove_tickets_from_owner(&ticket.owner_id, &vec![ticket_id]);
+        self.internal_remove_tickets_from_owner(
+            ticket.raffle_id,
+            &ticket.owner_id,
+            &vec![ticket_id],
+        );
         // Add the ticket to the receiver_id's set.
-        self.internal_add_tickets_to_owner(receiver_id, &vec![ticket_id]);
+        self.internal_add_tickets_to_owner(ticket.raffle_id, receiver_id, &vec![ticket_id]);
 
         // Create a new ticket struct.
         let new_ticket = Ticket {
@@ -249,3 +990,35 @@ impl Contract {
         ticket
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn draw_indices_are_distinct_and_deterministic() {
+        let indices = draw_indices(b"seed", 10, 10, &[]);
+        let unique: HashSet<u64> = indices.iter().cloned().collect();
+        assert_eq!(unique, (0..10).collect());
+        assert_eq!(indices, draw_indices(b"seed", 10, 10, &[]));
+    }
+
+    #[test]
+    fn draw_indices_skip_excluded() {
+        let excluded = draw_indices(b"first round", 10, 4, &[]);
+        let indices = draw_indices(b"second round", 10, 6, &excluded);
+        let unique: HashSet<u64> = indices.iter().cloned().collect();
+        assert_eq!(unique.len(), 6);
+        assert!(excluded.iter().all(|index| !unique.contains(index)));
+    }
+
+    #[test]
+    fn mul_div_handles_products_past_u128() {
+        assert_eq!(mul_div(6, 7, 4), 10);
+        assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX), u128::MAX);
+        assert_eq!(mul_div(u128::MAX / 3, u128::MAX, u128::MAX), u128::MAX / 3);
+        let amount = 10u128.pow(30);
+        assert_eq!(mul_div(amount / 4, amount / 2, amount), amount / 8);
+    }
+}
@@ -0,0 +1,454 @@
+use crate::*;
+use near_sdk::json_types::U64;
+
+/// Injected Keypom Args struct to be sent to external contracts
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct KeypomArgs {
+    pub account_id_field: Option<String>,
+    pub funder_id_field: Option<String>,
+    pub drop_id_field: Option<String>,
+    pub key_id_field: Option<String>,
+}
+
+// A drop or key ID injected by Keypom. Keypom v2 injects numeric IDs while v3 drop IDs are strings,
+// so both are accepted and compared in their string form.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(untagged)]
+pub enum KeypomId {
+    Number(u64),
+    Text(String),
+}
+
+impl std::fmt::Display for KeypomId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeypomId::Number(id) => write!(f, "{}", id),
+            KeypomId::Text(id) => write!(f, "{}", id),
+        }
+    }
+}
+
+// The Keypom key that was used to mint a ticket.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct KeypomKey {
+    pub funder_id: AccountId, // Account that funded the drop
+    pub drop_id: String,      // Drop the key belongs to
+    pub key_id: String,       // ID of the key within the drop
+}
+
+// A Keypom drop that is allowed to mint tickets into a raffle.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct KeypomDrop {
+    pub funder_id: AccountId,           // Account that funded the drop
+    pub drop_id: String,                // ID of the drop
+    pub tickets_per_claim: Option<u64>, // Tickets each claim mints, or Keypom's ticket_amount if unset
+    pub max_tickets: Option<u64>,       // Maximum number of tickets the drop can mint
+    pub minted: u64,                    // Tickets the drop has minted so far
+}
+
+// Make sure Keypom injected its args into the arguments mint_ticket expects.
+// Otherwise a caller could get Keypom to write the drop or funder into a different argument.
+pub(crate) fn assert_keypom_args(keypom_args: &KeypomArgs) {
+    assert_keypom_field(
+        "account_id_field",
+        &keypom_args.account_id_field,
+        "receiver_id",
+    );
+    assert_keypom_field("funder_id_field", &keypom_args.funder_id_field, "funder_id");
+    assert_keypom_field("drop_id_field", &keypom_args.drop_id_field, "drop_id");
+    assert_keypom_field("key_id_field", &keypom_args.key_id_field, "key_id");
+}
+
+fn assert_keypom_field(name: &str, field: &Option<String>, expected: &str) {
+    let field = field
+        .as_deref()
+        .unwrap_or_else(|| panic!("Missing keypom_args.{}", name));
+    require!(
+        field == expected,
+        format!(
+            "Malicious call. keypom_args.{} must be \"{}\", not \"{}\"",
+            name, expected, field
+        )
+    );
+}
+
+// Check the Keypom fields passed to mint_ticket and return the key that is minting, if any.
+// Mints without keypom_args come from approved minters that aren't Keypom.
+pub(crate) fn keypom_key_from_args(
+    drop_id: Option<KeypomId>,
+    funder_id: Option<AccountId>,
+    key_id: Option<KeypomId>,
+    keypom_args: Option<KeypomArgs>,
+) -> Option<KeypomKey> {
+    let keypom_args = match keypom_args {
+        Some(keypom_args) => keypom_args,
+        None => {
+            require!(
+                drop_id.is_none() && funder_id.is_none() && key_id.is_none(),
+                "drop_id, funder_id and key_id can only be passed along with keypom_args"
+            );
+            return None;
+        }
+    };
+    assert_keypom_args(&keypom_args);
+
+    Some(KeypomKey {
+        funder_id: funder_id.expect("Keypom did not pass a funder_id"),
+        drop_id: drop_id.expect("Keypom did not pass a drop_id").to_string(),
+        key_id: key_id.expect("Keypom did not pass a key_id").to_string(),
+    })
+}
+
+// Create the allowlist entry for the drop a raffle was created with, with no cap until the raffle
+// owner changes that with add_keypom_drop. Without tickets_per_claim, each claim mints the
+// ticket_amount set in the drop's call args, as drops did before the allowlist.
+// Returns no drops unless both the funder and the drop are given.
+pub(crate) fn initial_keypom_drops(
+    funder_id: Option<AccountId>,
+    drop_id: Option<String>,
+    tickets_per_claim: Option<u64>,
+) -> Vec<KeypomDrop> {
+    match (funder_id, drop_id) {
+        (Some(funder_id), Some(drop_id)) => vec![KeypomDrop {
+            funder_id,
+            drop_id,
+            tickets_per_claim,
+            max_tickets: None,
+            minted: 0,
+        }],
+        _ => vec![],
+    }
+}
+
+// Work out how many tickets a Keypom claim mints into the raffle. Each drop on the allowlist
+// mints its own tickets_per_claim, or the ticket_amount Keypom passed if it has none, up to its cap.
+pub(crate) fn keypom_ticket_amount(
+    raffle: &mut Raffle,
+    keypom_key: &KeypomKey,
+    ticket_amount: u64,
+) -> u64 {
+    let drop = raffle
+        .keypom_drops
+        .iter_mut()
+        .find(|drop| drop.funder_id == keypom_key.funder_id && drop.drop_id == keypom_key.drop_id)
+        .expect("Drop is not allowed to mint tickets for this raffle");
+    let ticket_amount = drop.tickets_per_claim.unwrap_or(ticket_amount);
+    if let Some(max_tickets) = drop.max_tickets {
+        require!(
+            drop.minted + ticket_amount <= max_tickets,
+            "Drop has minted all of its tickets for this raffle"
+        );
+    }
+    drop.minted += ticket_amount;
+
+    ticket_amount
+}
+
+impl Contract {
+    // Remember that a Keypom key minted into a raffle, refusing keys that already have.
+    // This stops replayed calls and keys with multiple uses from minting more than once.
+    pub(crate) fn internal_use_keypom_key(&mut self, raffle_id: RaffleId, keypom_key: &KeypomKey) {
+        require!(
+            self.used_keypom_keys.insert(&(
+                raffle_id,
+                keypom_key.drop_id.clone(),
+                keypom_key.key_id.clone()
+            )),
+            "This Keypom key has already minted tickets for this raffle"
+        );
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Allow another Keypom drop to mint tickets into a raffle, or update the settings of one
+    // that is already allowed. Each claim from the drop mints tickets_per_claim tickets.
+    #[payable]
+    pub fn add_keypom_drop(
+        &mut self,
+        raffle_id: U64,
+        funder_id: AccountId,
+        drop_id: KeypomId,
+        tickets_per_claim: u64,
+        max_tickets: Option<u64>,
+    ) {
+        // Measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        self.assert_raffle_owner(&raffle);
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Draft | RaffleStatus::Open
+            ),
+            "Raffle is no longer accepting tickets"
+        );
+        require!(tickets_per_claim > 0, "tickets_per_claim must be positive");
+
+        let drop_id = drop_id.to_string();
+        match raffle
+            .keypom_drops
+            .iter_mut()
+            .find(|drop| drop.funder_id == funder_id && drop.drop_id == drop_id)
+        {
+            Some(drop) => {
+                drop.tickets_per_claim = Some(tickets_per_claim);
+                drop.max_tickets = max_tickets;
+            }
+            None => raffle.keypom_drops.push(KeypomDrop {
+                funder_id,
+                drop_id,
+                tickets_per_claim: Some(tickets_per_claim),
+                max_tickets,
+                minted: 0,
+            }),
+        }
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+
+        // Refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
+        refund_deposit(env::storage_usage().saturating_sub(initial_storage_usage));
+    }
+
+    // Stop a Keypom drop from minting tickets into a raffle. Tickets it already minted are kept.
+    pub fn remove_keypom_drop(&mut self, raffle_id: U64, funder_id: AccountId, drop_id: KeypomId) {
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        self.assert_raffle_owner(&raffle);
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Draft | RaffleStatus::Open
+            ),
+            "Raffle is no longer accepting tickets"
+        );
+
+        let drop_id = drop_id.to_string();
+        let num_drops = raffle.keypom_drops.len();
+        raffle
+            .keypom_drops
+            .retain(|drop| drop.funder_id != funder_id || drop.drop_id != drop_id);
+        require!(
+            raffle.keypom_drops.len() < num_drops,
+            "Drop is not allowed to mint tickets for this raffle"
+        );
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+    }
+
+    // Get the Keypom drops that are allowed to mint tickets into a raffle
+    pub fn get_keypom_drops(&self, raffle_id: U64) -> Vec<KeypomDrop> {
+        self.raffle_by_id
+            .get(&raffle_id.0)
+            .map(|raffle| raffle.keypom_drops)
+            .unwrap_or_default()
+    }
+
+    // Check whether a Keypom key has already minted tickets into a raffle
+    pub fn is_keypom_key_used(&self, raffle_id: U64, drop_id: KeypomId, key_id: KeypomId) -> bool {
+        self.used_keypom_keys
+            .contains(&(raffle_id.0, drop_id.to_string(), key_id.to_string()))
+    }
+
+    // Get the Keypom key that minted a ticket, if it was minted through Keypom
+    pub fn get_ticket_keypom_key(&self, token_id: U64) -> Option<KeypomKey> {
+        self.keypom_key_by_ticket.get(&token_id.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn keypom_args() -> KeypomArgs {
+        KeypomArgs {
+            account_id_field: Some("receiver_id".to_string()),
+            funder_id_field: Some("funder_id".to_string()),
+            drop_id_field: Some("drop_id".to_string()),
+            key_id_field: Some("key_id".to_string()),
+        }
+    }
+
+    #[test]
+    fn keypom_ids_from_either_layout() {
+        let v2: KeypomId = near_sdk::serde_json::from_str("1682012345678").unwrap();
+        let v3: KeypomId = near_sdk::serde_json::from_str(r#""aha-conference""#).unwrap();
+        assert_eq!(v2.to_string(), "1682012345678");
+        assert_eq!(v3.to_string(), "aha-conference");
+    }
+
+    #[test]
+    fn accepts_expected_keypom_args() {
+        assert_keypom_args(&keypom_args());
+    }
+
+    #[test]
+    #[should_panic(expected = "keypom_args.key_id_field must be \"key_id\"")]
+    fn rejects_redirected_keypom_args() {
+        let mut args = keypom_args();
+        args.key_id_field = Some("ticket_amount".to_string());
+        assert_keypom_args(&args);
+    }
+
+    #[test]
+    #[should_panic(expected = "Missing keypom_args.drop_id_field")]
+    fn rejects_missing_keypom_args() {
+        let mut args = keypom_args();
+        args.drop_id_field = None;
+        assert_keypom_args(&args);
+    }
+
+    fn funder() -> AccountId {
+        "funder.near".parse().unwrap()
+    }
+
+    // Set up an open raffle that the "conference" drop can mint two tickets per claim into, up to four
+    fn setup_keypom_raffle() -> Contract {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.add_keypom_drop(
+            U64(RAFFLE_ID),
+            funder(),
+            KeypomId::Text("conference".to_string()),
+            2,
+            Some(4),
+        );
+
+        contract
+    }
+
+    fn claim(contract: &mut Contract, drop_id: &str, key_id: u64) {
+        claim_with_amount(contract, drop_id, key_id, 1);
+    }
+
+    // Claim a key from a drop whose call args set ticket_amount
+    fn claim_with_amount(contract: &mut Contract, drop_id: &str, key_id: u64, ticket_amount: u64) {
+        set_caller(&minter(), STORAGE_DEPOSIT);
+        contract.mint_ticket(
+            U64(RAFFLE_ID),
+            user(0),
+            ticket_amount,
+            Some(KeypomId::Text(drop_id.to_string())),
+            Some(funder()),
+            Some(KeypomId::Number(key_id)),
+            Some(keypom_args()),
+        );
+    }
+
+    #[test]
+    fn keypom_claims_mint_the_drops_tickets_per_claim() {
+        let mut contract = setup_keypom_raffle();
+        claim(&mut contract, "conference", 7);
+
+        assert_eq!(contract.nft_supply_for_owner(user(0)).0, 2);
+        assert_eq!(contract.get_keypom_drops(U64(RAFFLE_ID))[0].minted, 2);
+        assert!(contract.is_keypom_key_used(
+            U64(RAFFLE_ID),
+            KeypomId::Text("conference".to_string()),
+            KeypomId::Number(7)
+        ));
+        let ticket_id = contract.nft_tokens_for_owner(user(0), None, None)[0].ticket_id;
+        let keypom_key = contract.get_ticket_keypom_key(ticket_id).unwrap();
+        assert_eq!(keypom_key.key_id, "7");
+    }
+
+    #[test]
+    #[should_panic(expected = "This Keypom key has already minted tickets for this raffle")]
+    fn keypom_keys_cannot_mint_twice() {
+        let mut contract = setup_keypom_raffle();
+        claim(&mut contract, "conference", 7);
+        claim(&mut contract, "conference", 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "Drop is not allowed to mint tickets for this raffle")]
+    fn drops_off_the_allowlist_are_refused() {
+        let mut contract = setup_keypom_raffle();
+        claim(&mut contract, "other-event", 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "Drop is not allowed to mint tickets for this raffle")]
+    fn removed_drops_are_refused() {
+        let mut contract = setup_keypom_raffle();
+        set_caller(&raffle_owner(), 0);
+        contract.remove_keypom_drop(
+            U64(RAFFLE_ID),
+            funder(),
+            KeypomId::Text("conference".to_string()),
+        );
+        claim(&mut contract, "conference", 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "Drop has minted all of its tickets for this raffle")]
+    fn drops_stop_minting_at_their_cap() {
+        let mut contract = setup_keypom_raffle();
+        claim(&mut contract, "conference", 1);
+        claim(&mut contract, "conference", 2);
+        claim(&mut contract, "conference", 3);
+    }
+
+    fn create_raffle_with_drop(
+        funder_id: Option<AccountId>,
+        drop_id: Option<String>,
+        tickets_per_claim: Option<u64>,
+    ) -> Contract {
+        set_caller(&contract_owner(), 0);
+        let mut contract = Contract::new_default_meta(contract_owner());
+        contract.add_approved_minter(minter());
+        set_caller(&contract_owner(), STORAGE_DEPOSIT);
+        contract.create_raffle(
+            RAFFLE_ID,
+            raffle_metadata(None),
+            funder_id,
+            drop_id,
+            tickets_per_claim,
+            None,
+            None,
+            None,
+            None,
+        );
+
+        contract
+    }
+
+    #[test]
+    fn drop_from_create_raffle_mints_its_tickets_per_claim() {
+        let mut contract =
+            create_raffle_with_drop(Some(funder()), Some("conference".to_string()), Some(3));
+        set_caller(&contract_owner(), 0);
+        contract.open_raffle(U64(RAFFLE_ID));
+
+        claim_with_amount(&mut contract, "conference", 7, 1);
+        assert_eq!(contract.nft_supply_for_owner(user(0)).0, 3);
+        assert_eq!(contract.get_keypom_drops(U64(RAFFLE_ID))[0].minted, 3);
+    }
+
+    #[test]
+    fn drops_without_tickets_per_claim_mint_the_ticket_amount() {
+        let mut contract =
+            create_raffle_with_drop(Some(funder()), Some("conference".to_string()), None);
+        set_caller(&contract_owner(), 0);
+        contract.open_raffle(U64(RAFFLE_ID));
+
+        claim_with_amount(&mut contract, "conference", 7, 5);
+        assert_eq!(contract.nft_supply_for_owner(user(0)).0, 5);
+        assert_eq!(contract.get_keypom_drops(U64(RAFFLE_ID))[0].minted, 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "funder_id and drop_id must be passed together")]
+    fn create_raffle_needs_both_keypom_ids() {
+        create_raffle_with_drop(Some(funder()), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "tickets_per_claim can only be passed along with a drop")]
+    fn create_raffle_needs_a_drop_for_tickets_per_claim() {
+        create_raffle_with_drop(None, None, Some(3));
+    }
+}
@@ -9,41 +9,98 @@ use near_sdk::{
 use std::collections::HashMap;
 
 pub use crate::approval::*;
+pub use crate::enumeration::*;
 pub use crate::events::*;
 use crate::internal::*;
+pub use crate::keypom::*;
 pub use crate::metadata::*;
+pub use crate::migrate::*;
 pub use crate::nft_core::*;
-pub use crate::owner::*;
+pub use crate::prize::*;
 pub use crate::royalty::*;
-pub use crate::raffle::*;
+pub use crate::sale::*;
 
 mod approval;
+mod burn;
+mod cancel;
+mod enumeration;
 mod events;
 mod internal;
+mod keypom;
 mod metadata;
+mod migrate;
 mod nft_core;
 mod owner;
+mod prize;
 mod royalty;
 mod raffle;
+mod sale;
+#[cfg(test)]
+mod test_utils;
 
 /// This spec can be treated like a version of the standard.
 pub const NFT_METADATA_SPEC: &str = "nft-1.0.0";
 /// This is the name of the NFT standard we're using
 pub const NFT_STANDARD_NAME: &str = "nep171";
+/// Version of the raffle events emitted by this contract
+pub const RAFFLE_SPEC: &str = "1.0.0";
+/// Name of the standard used for raffle events
+pub const RAFFLE_STANDARD_NAME: &str = "aha_raffle";
 
 // Represents the raffle type. All tokens will derive this data.
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Raffle {
-    // If specified, the drop funder coming from Keypom MUST be equal to this value
-    funder_id: Option<AccountId>,
-    // If specified, the drop ID coming from Keypom MUST be equal to this value
-    drop_id: Option<String>,
+    // Keypom drops that can mint tickets into the raffle
+    keypom_drops: Vec<KeypomDrop>,
     // Metadata including title, num copies, etc., that all tokens will derive from
     metadata: RaffleMetadata,
     // Royalty used for all tokens in the collection
     royalty: Option<HashMap<AccountId, u32>>,
+    // Whether tickets can be transferred or approved for transfer by their owners
+    transferable: bool,
     // Set of tickets in the collection
     tickets: UnorderedSet<TicketId>,
+    // Current lifecycle state of the raffle
+    status: RaffleStatus,
+    // If specified, the raffle automatically opens at this block timestamp (in nanoseconds)
+    starts_at: Option<u64>,
+    // If specified, the raffle automatically closes at this block timestamp (in nanoseconds)
+    ends_at: Option<u64>,
+    // If specified, the number of winners to draw. Anyone can draw once the raffle has ended with this many
+    num_winners: Option<u64>,
+    // If specified, the sha256 hash of a secret the owner must reveal to draw the raffle
+    draw_commitment: Option<Vec<u8>>,
+    // Record of the inputs used for every draw round, starting with the initial draw
+    draw_rounds: Vec<DrawTranscript>,
+    // If specified, winners must claim their prize before this block timestamp (in nanoseconds)
+    claim_deadline: Option<u64>,
+    // Ordered prize tiers. Winners are assigned to them in draw order
+    prize_tiers: Vec<PrizeTier>,
+    // Winning tickets, in the order they were drawn
+    winners: Vec<Winner>,
+    // NFT prizes held in escrow. The prize at index i goes to the i-th winner
+    nft_prizes: Vec<NftPrize>,
+    // NFTs the owner registered as prizes that haven't been sent into escrow yet, by contract and token ID
+    expected_nft_prizes: Vec<(AccountId, String)>,
+    // NEAR attached by the owner through fund_raffle, to be split between the winners
+    near_prize_pool: Balance,
+    // Share of the NEAR prize pool for each winner in draw order, in basis points (10_000 = 100%)
+    prize_split: Vec<u32>,
+    // Fungible token prize pools held in escrow, keyed by token contract
+    ft_prize_pools: HashMap<AccountId, FtPrizePool>,
+    // If specified, anyone can buy tickets for this price (in yoctoNEAR) through buy_tickets
+    ticket_price: Option<Balance>,
+    // If specified, anyone can buy tickets by sending this token through ft_transfer_call
+    ft_ticket_price: Option<FtTicketPrice>,
+    // Account that receives the ticket sale proceeds. Defaults to the raffle owner
+    beneficiary_id: Option<AccountId>,
+    // Share of the ticket sale proceeds for each charity, in basis points (10_000 = 100%)
+    charity_split: HashMap<AccountId, u32>,
+    // Ticket sale proceeds held until the raffle is drawn, in NEAR and per token contract
+    near_proceeds: Balance,
+    ft_proceeds: HashMap<AccountId, Balance>,
+    // Payouts of the proceeds that failed, paid again by the next withdraw_proceeds
+    unpaid_proceeds: Vec<UnpaidProceeds>,
     // Owner of the raffle
     owner_id: AccountId
 }
@@ -59,13 +116,31 @@ pub struct Contract {
     pub approved_minters: LookupSet<AccountId>,
     // Approved users that can create raffles
     pub approved_creators: LookupSet<AccountId>,
+    // Limits placed on specific raffle creators
+    pub creator_limits: LookupMap<AccountId, CreatorLimits>,
+    // Number of raffles each creator has that haven't been settled yet
+    pub active_raffles_per_creator: LookupMap<AccountId, u64>,
     // Map the collection ID (stored in Token obj) to the collection data
     pub raffle_by_id: UnorderedMap<CollectionId, Raffle>,
     // Keeps track of the token struct for a given token ID
     pub ticket_by_id: UnorderedMap<TicketId, Ticket>,
+    // ID that will be given to the next ticket minted, across all raffles
+    pub next_ticket_id: TicketId,
 
     // Keeps track of all the token IDs for a given account
     pub tickets_per_owner: LookupMap<AccountId, UnorderedSet<TicketId>>,
+    // Number of tickets each account holds in each raffle
+    pub tickets_per_raffle_owner: LookupMap<(RaffleId, AccountId), u64>,
+    // What was paid for each ticket that was bought rather than minted for free
+    pub ticket_payments: LookupMap<TicketId, TicketPayment>,
+    // Keypom key that minted each ticket, for tickets minted through Keypom
+    pub keypom_key_by_ticket: LookupMap<TicketId, KeypomKey>,
+    // Keypom keys, by drop and key ID, that have already minted tickets into each raffle
+    pub used_keypom_keys: LookupSet<(RaffleId, String, String)>,
+    // Account that paid for the storage of each ticket. Tickets the contract paid for have no entry
+    pub storage_payer_by_ticket: LookupMap<TicketId, AccountId>,
+    // NEAR each account deposited to cover the storage of tickets it buys with fungible tokens
+    pub storage_deposits: LookupMap<AccountId, Balance>,
     // Keeps track of the metadata for the contract
     pub metadata: LazyOption<NFTContractMetadata>,
 }
@@ -82,6 +157,14 @@ pub enum StorageKey {
     TicketsPerOwner,
     TicketsPerOwnerInner { account_id_hash: CryptoHash },
     Metadata,
+    CreatorLimits,
+    ActiveRafflesPerCreator,
+    TicketsPerRaffleOwner,
+    TicketPayments,
+    KeypomKeyByTicket,
+    UsedKeypomKeys,
+    StoragePayerByTicket,
+    StorageDeposits,
 }
 
 #[near_bindgen]
@@ -118,23 +201,38 @@ impl Contract {
             LookupSet::new(StorageKey::ApprovedCreators.try_to_vec().unwrap());
         approved_creators.insert(&owner_id);
 
-        let this = Self {
+        Self {
             owner_id,
             approved_minters,
             approved_creators,
+            creator_limits: LookupMap::new(StorageKey::CreatorLimits.try_to_vec().unwrap()),
+            active_raffles_per_creator: LookupMap::new(
+                StorageKey::ActiveRafflesPerCreator.try_to_vec().unwrap(),
+            ),
             raffle_by_id: UnorderedMap::new(StorageKey::RaffleById.try_to_vec().unwrap()),
             ticket_by_id: UnorderedMap::new(
                 StorageKey::TicketById.try_to_vec().unwrap(),
             ),
+            next_ticket_id: 0,
             tickets_per_owner: LookupMap::new(
                 StorageKey::TicketsPerOwner.try_to_vec().unwrap(),
             ),
+            tickets_per_raffle_owner: LookupMap::new(
+                StorageKey::TicketsPerRaffleOwner.try_to_vec().unwrap(),
+            ),
+            ticket_payments: LookupMap::new(StorageKey::TicketPayments.try_to_vec().unwrap()),
+            keypom_key_by_ticket: LookupMap::new(
+                StorageKey::KeypomKeyByTicket.try_to_vec().unwrap(),
+            ),
+            used_keypom_keys: LookupSet::new(StorageKey::UsedKeypomKeys.try_to_vec().unwrap()),
+            storage_payer_by_ticket: LookupMap::new(
+                StorageKey::StoragePayerByTicket.try_to_vec().unwrap(),
+            ),
+            storage_deposits: LookupMap::new(StorageKey::StorageDeposits.try_to_vec().unwrap()),
             metadata: LazyOption::new(
                 StorageKey::Metadata.try_to_vec().unwrap(),
                 Some(&metadata),
             ),
-        };
-
-        this
+        }
     }
 }
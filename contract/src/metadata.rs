@@ -31,7 +31,38 @@ pub struct RaffleMetadata {
     
     // EXTRA
     pub max_tickets: Option<u64>, // Maximum number of tickets that can be minted
-    pub live_tickets: u64 // Total of tickets currently live
+    pub live_tickets: u64, // Total of tickets currently live
+    pub max_tickets_per_account: Option<u64>, // Maximum number of tickets a single account can hold
+}
+
+// Everything that went into a draw round, so that anyone can re-derive the winners.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DrawTranscript {
+    pub block_height: U64,               // Block the draw happened in
+    pub block_seed: Base64VecU8,         // env::random_seed() of that block
+    pub commitment: Option<Base64VecU8>, // sha256 of the owner's secret, if the round used commit-reveal
+    pub secret: Option<Base64VecU8>,     // The revealed secret, if the round used commit-reveal
+    pub seed: Base64VecU8,               // Seed the winners were drawn with
+    pub num_tickets: U64,                // Number of tickets the winners were drawn from
+    pub num_winners: U64,                // Number of winners that were drawn
+    pub indices: Vec<U64>, // Positions of the drawn tickets in the raffle's ticket list
+    pub ticket_ids: Vec<U64>, // IDs of the drawn tickets, in draw order
+    pub slots: Vec<U64>,   // Positions in the winners list that this round filled
+}
+
+// Lifecycle of a raffle. Raffles move forward through these states one step at a time.
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum RaffleStatus {
+    Draft,     // Created but not yet accepting tickets
+    Open,      // Tickets can be minted
+    Closed,    // Ticket sales are frozen and the raffle is waiting to be drawn
+    Drawn,     // Winners have been drawn
+    Settled,   // Prizes have been handed out and the raffle is finished
+    Cancelled, // The raffle was called off. Prizes and ticket payments can be reclaimed
 }
 
 #[derive(BorshDeserialize, BorshSerialize)]
@@ -46,6 +77,116 @@ pub struct Ticket {
     pub next_approval_id: u64,
 }
 
+// Limits the contract owner can place on an approved raffle creator. Unset fields mean no limit.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CreatorLimits {
+    pub max_active_raffles: Option<u64>, // Maximum number of raffles that haven't been settled yet
+    pub max_tickets_per_raffle: Option<u64>, // Maximum value for max_tickets on each raffle
+}
+
+// An NFT from another contract held in escrow as a raffle prize.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftPrize {
+    pub nft_contract_id: AccountId, // Contract the NFT lives on
+    pub token_id: String,           // Token ID on that contract
+    pub depositor_id: AccountId,    // Account that sent the NFT into escrow
+    pub claimed: bool,              // Whether the NFT has been sent to a winner
+}
+
+// Fungible tokens from a single token contract held in escrow as a raffle prize pool.
+// The pool is split evenly between the winners.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtPrizePool {
+    pub amount: U128,                         // Total amount deposited into the pool
+    pub min_deposit: U128,                    // Smallest amount a single deposit can add
+    pub depositors: HashMap<AccountId, U128>, // Amount deposited by each account
+    pub claimed: Vec<U64>,                    // Winning tickets that have claimed their share
+}
+
+impl FtPrizePool {
+    pub fn new(min_deposit: Balance) -> Self {
+        Self {
+            amount: U128(0),
+            min_deposit: U128(min_deposit),
+            depositors: HashMap::new(),
+            claimed: vec![],
+        }
+    }
+}
+
+// Fungible token a raffle accepts for ticket purchases, and the price of a single ticket.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtTicketPrice {
+    pub token_id: AccountId, // Token contract tickets are paid with
+    pub price: U128,         // Price of one ticket in the token's smallest unit
+}
+
+// What was paid for a ticket, so it can be refunded if the raffle is cancelled.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TicketPayment {
+    pub token_id: Option<AccountId>, // Token contract the ticket was paid with, or None for NEAR
+    pub amount: U128,                // Price paid for the ticket
+}
+
+// Share of the ticket sale proceeds whose payout failed, waiting to be withdrawn again.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct UnpaidProceeds {
+    pub token_id: Option<AccountId>, // Token contract the share is paid in, or None for NEAR
+    pub recipient_id: AccountId,     // Charity or beneficiary the share is for
+    pub amount: U128,                // Amount of the share
+}
+
+// Escrowed asset that each winner in a prize tier receives.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub enum PrizeAsset {
+    // Escrowed NFTs from one contract, handed to the tier's winners in order
+    Nft {
+        nft_contract_id: AccountId,
+        token_ids: Vec<String>,
+    },
+    // Amount per winner out of the raffle's prize pool for a fungible token
+    Ft {
+        token_id: AccountId,
+        amount: U128,
+    },
+    // Amount per winner out of the raffle's NEAR prize pool
+    Near {
+        amount: U128,
+    },
+}
+
+// A prize tier of a raffle. Winners are assigned to tiers in order, `quantity` at a time.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PrizeTier {
+    pub title: String,             // ex. "Grand Prize"
+    pub media: Option<String>,     // URL to an image of the prize
+    pub quantity: u64,             // Number of winners in this tier
+    pub asset: Option<PrizeAsset>, // Escrowed asset each winner receives, if it is handed out on-chain
+}
+
+// A ticket that was drawn as a winner, along with the account that owned it at draw time.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Winner {
+    pub ticket_id: U64,
+    pub owner_id: AccountId,
+    // Index of the prize tier the ticket was assigned to, if the raffle has tiers
+    pub prize_tier: Option<u64>,
+    // Share of the raffle's NEAR prize pool won by this ticket
+    pub near_prize: U128,
+    pub near_prize_claimed: bool,
+    // Whether the ticket's owner claimed the prize. Unclaimed prizes can be redrawn after the deadline
+    pub claimed: bool,
+}
+
 //The Json token is what will be returned from view calls.
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
@@ -0,0 +1,242 @@
+use crate::*;
+
+// Contract state from before ticket IDs were tracked with a contract-wide counter.
+// Fields added to the contract since then start out empty, and raffles are rewritten
+// from the old layout below.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldContract {
+    pub owner_id: AccountId,
+    pub approved_minters: LookupSet<AccountId>,
+    pub approved_creators: LookupSet<AccountId>,
+    pub raffle_by_id: UnorderedMap<CollectionId, OldRaffle>,
+    pub ticket_by_id: UnorderedMap<TicketId, Ticket>,
+    pub tickets_per_owner: LookupMap<AccountId, UnorderedSet<TicketId>>,
+    pub metadata: LazyOption<NFTContractMetadata>,
+}
+
+// Raffle layout from before the raffle lifecycle, prizes and ticket sales were added
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldRaffle {
+    pub funder_id: Option<AccountId>,
+    pub drop_id: Option<String>,
+    pub metadata: OldRaffleMetadata,
+    pub royalty: Option<HashMap<AccountId, u32>>,
+    pub tickets: UnorderedSet<TicketId>,
+    pub owner_id: AccountId,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldRaffleMetadata {
+    pub title: String,
+    pub description: String,
+    pub media: String,
+    pub max_tickets: Option<u64>,
+    pub live_tickets: u64,
+}
+
+impl From<OldRaffle> for Raffle {
+    // Existing raffles stay open for minting with transferable tickets, as they were before the upgrade.
+    // Their Keypom drop goes on the allowlist and keeps minting the ticket_amount set in its call args.
+    // Raffles that only named a funder or only a drop have to add their drop again with add_keypom_drop.
+    fn from(old: OldRaffle) -> Self {
+        Self {
+            keypom_drops: initial_keypom_drops(old.funder_id, old.drop_id, None),
+            metadata: RaffleMetadata {
+                title: old.metadata.title,
+                description: old.metadata.description,
+                media: old.metadata.media,
+                max_tickets: old.metadata.max_tickets,
+                // The old contract never kept live_tickets up to date
+                live_tickets: old.tickets.len(),
+                max_tickets_per_account: None,
+            },
+            royalty: old.royalty,
+            transferable: true,
+            tickets: old.tickets,
+            status: RaffleStatus::Open,
+            starts_at: None,
+            ends_at: None,
+            num_winners: None,
+            draw_commitment: None,
+            draw_rounds: Vec::new(),
+            claim_deadline: None,
+            prize_tiers: Vec::new(),
+            winners: Vec::new(),
+            nft_prizes: Vec::new(),
+            expected_nft_prizes: Vec::new(),
+            near_prize_pool: 0,
+            prize_split: Vec::new(),
+            ft_prize_pools: HashMap::new(),
+            ticket_price: None,
+            ft_ticket_price: None,
+            beneficiary_id: None,
+            charity_split: HashMap::new(),
+            near_proceeds: 0,
+            ft_proceeds: HashMap::new(),
+            unpaid_proceeds: Vec::new(),
+            owner_id: old.owner_id,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // Migrate the contract state after upgrading from a version without the ticket ID counter.
+    // Existing tickets keep their IDs and new tickets are numbered after the highest one.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let mut old_state: OldContract = env::state_read().expect("No contract state");
+
+        // In a single pass over the existing tickets, start counting after the highest ticket ID
+        // that was already minted and rebuild the per-raffle ticket counts
+        let mut next_ticket_id = 0;
+        let mut tickets_per_raffle_owner =
+            LookupMap::new(StorageKey::TicketsPerRaffleOwner.try_to_vec().unwrap());
+        for (ticket_id, ticket) in old_state.ticket_by_id.iter() {
+            next_ticket_id = next_ticket_id.max(ticket_id + 1);
+            let key = (ticket.raffle_id, ticket.owner_id);
+            let count = tickets_per_raffle_owner.get(&key).unwrap_or(0);
+            tickets_per_raffle_owner.insert(&key, &(count + 1));
+        }
+
+        // Rewrite every raffle into the current layout, in place. The old map is cleared first,
+        // since the new one shares its prefix and would otherwise find the old keys' indices.
+        let old_raffles: Vec<(CollectionId, OldRaffle)> = old_state.raffle_by_id.iter().collect();
+        old_state.raffle_by_id.clear();
+        let mut raffle_by_id = UnorderedMap::new(StorageKey::RaffleById.try_to_vec().unwrap());
+        // Migrated raffles are open, so they count towards their creator's active raffles
+        let mut active_raffles_per_creator =
+            LookupMap::new(StorageKey::ActiveRafflesPerCreator.try_to_vec().unwrap());
+        for (raffle_id, old_raffle) in old_raffles {
+            let active_raffles = active_raffles_per_creator
+                .get(&old_raffle.owner_id)
+                .unwrap_or(0);
+            active_raffles_per_creator.insert(&old_raffle.owner_id, &(active_raffles + 1));
+            raffle_by_id.insert(&raffle_id, &Raffle::from(old_raffle));
+        }
+
+        Self {
+            owner_id: old_state.owner_id,
+            approved_minters: old_state.approved_minters,
+            approved_creators: old_state.approved_creators,
+            creator_limits: LookupMap::new(StorageKey::CreatorLimits.try_to_vec().unwrap()),
+            active_raffles_per_creator,
+            raffle_by_id,
+            ticket_by_id: old_state.ticket_by_id,
+            next_ticket_id,
+            tickets_per_owner: old_state.tickets_per_owner,
+            tickets_per_raffle_owner,
+            ticket_payments: LookupMap::new(StorageKey::TicketPayments.try_to_vec().unwrap()),
+            keypom_key_by_ticket: LookupMap::new(
+                StorageKey::KeypomKeyByTicket.try_to_vec().unwrap(),
+            ),
+            used_keypom_keys: LookupSet::new(StorageKey::UsedKeypomKeys.try_to_vec().unwrap()),
+            storage_payer_by_ticket: LookupMap::new(
+                StorageKey::StoragePayerByTicket.try_to_vec().unwrap(),
+            ),
+            storage_deposits: LookupMap::new(StorageKey::StorageDeposits.try_to_vec().unwrap()),
+            metadata: old_state.metadata,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::json_types::U64;
+
+    fn funder() -> AccountId {
+        "funder.near".parse().unwrap()
+    }
+
+    fn old_ticket(owner_id: AccountId) -> Ticket {
+        Ticket {
+            raffle_id: RAFFLE_ID,
+            owner_id,
+            approved_account_ids: Default::default(),
+            next_approval_id: 0,
+        }
+    }
+
+    // Write the state of an old contract with one raffle, holding tickets 0 and 5 for user(0)
+    fn write_old_state() {
+        set_caller(&contract_id(), 0);
+        let mut approved_minters =
+            LookupSet::new(StorageKey::ApprovedMinters.try_to_vec().unwrap());
+        approved_minters.insert(&minter());
+
+        let mut tickets = UnorderedSet::new(StorageKey::RaffleTickets {
+            raffle_id_hash: hash_account_id(&format!("{}{}", RAFFLE_ID, raffle_owner())),
+        });
+        let mut ticket_by_id = UnorderedMap::new(StorageKey::TicketById.try_to_vec().unwrap());
+        for ticket_id in [0, 5] {
+            tickets.insert(&ticket_id);
+            ticket_by_id.insert(&ticket_id, &old_ticket(user(0)));
+        }
+
+        let mut raffle_by_id = UnorderedMap::new(StorageKey::RaffleById.try_to_vec().unwrap());
+        raffle_by_id.insert(
+            &RAFFLE_ID,
+            &OldRaffle {
+                funder_id: Some(funder()),
+                drop_id: Some("conference".to_string()),
+                metadata: OldRaffleMetadata {
+                    title: "Raffle".to_string(),
+                    description: "A raffle".to_string(),
+                    media: "https://example.com/raffle.png".to_string(),
+                    max_tickets: None,
+                    // Stale, since the old contract never updated it
+                    live_tickets: 0,
+                },
+                royalty: None,
+                tickets,
+                owner_id: raffle_owner(),
+            },
+        );
+
+        env::state_write(&OldContract {
+            owner_id: contract_owner(),
+            approved_minters,
+            approved_creators: LookupSet::new(StorageKey::ApprovedCreators.try_to_vec().unwrap()),
+            raffle_by_id,
+            ticket_by_id,
+            tickets_per_owner: LookupMap::new(StorageKey::TicketsPerOwner.try_to_vec().unwrap()),
+            metadata: LazyOption::new(StorageKey::Metadata.try_to_vec().unwrap(), None),
+        });
+    }
+
+    #[test]
+    fn migrate_rewrites_old_raffles() {
+        write_old_state();
+        let contract = Contract::migrate();
+
+        let raffle = raffle(&contract);
+        assert_eq!(raffle_status(&raffle), RaffleStatus::Open);
+        assert!(raffle.transferable);
+        // live_tickets is rebuilt from the tickets rather than copied over
+        assert_eq!(raffle.metadata.live_tickets, 2);
+        assert_eq!(raffle.tickets.len(), 2);
+        assert_eq!(raffle.keypom_drops.len(), 1);
+        assert_eq!(raffle.keypom_drops[0].funder_id, funder());
+        assert_eq!(raffle.keypom_drops[0].drop_id, "conference");
+        // The drop keeps minting the ticket_amount set in its call args
+        assert_eq!(raffle.keypom_drops[0].tickets_per_claim, None);
+        assert_eq!(
+            contract
+                .nft_supply_for_raffle_owner(U64(RAFFLE_ID), user(0))
+                .0,
+            2
+        );
+        assert_eq!(contract.get_active_raffles_for_creator(raffle_owner()), 1);
+    }
+
+    #[test]
+    fn migrated_raffles_keep_minting_after_the_highest_ticket() {
+        write_old_state();
+        let mut contract = Contract::migrate();
+        mint(&mut contract, &user(1), 1);
+        assert_eq!(contract.nft_token(U64(6)).unwrap().owner_id, user(1));
+    }
+}
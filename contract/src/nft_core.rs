@@ -34,7 +34,7 @@ pub trait NonFungibleTokenCore {
 
 // External contract trait for NonFungibleTokenReceiver
 #[ext_contract(ext_non_fungible_token_receiver)]
-trait NonFungibleTokenReceiver {
+pub trait NonFungibleTokenReceiver {
     fn nft_on_transfer(
         &mut self,
         sender_id: AccountId,
@@ -46,7 +46,7 @@ trait NonFungibleTokenReceiver {
 
 // External contract trait for NonFungibleTokenResolver
 #[ext_contract(ext_self)]
-trait NonFungibleTokenResolver {
+pub trait NonFungibleTokenResolver {
     fn nft_resolve_transfer(
         &mut self,
         authorized_id: Option<String>,
@@ -126,7 +126,7 @@ impl NonFungibleTokenCore for Contract {
             .nft_on_transfer(
                 sender_id,
                 previous_ticket.owner_id.clone(),
-                token_id.clone(),
+                token_id,
                 msg,
             )
             .then(
@@ -152,7 +152,7 @@ impl NonFungibleTokenCore for Contract {
                 .raffle_by_id
                 .get(&ticket.raffle_id)
                 .expect("No raffle found");
-            let mut metadata = cur_series.metadata;
+            let metadata = cur_series.metadata;
 
             // Return the JsonToken wrapped in Some since we return an option
             Some(JsonTicket {
@@ -209,10 +209,20 @@ impl NonFungibleTokenResolver for Contract {
             return true;
         };
 
+        // If the raffle closed in the meantime, ownership is frozen and the token stays with the receiver
+        let raffle = self
+            .raffle_by_id
+            .get(&token.raffle_id)
+            .expect("Not a raffle");
+        if tickets_locked(&raffle) {
+            refund_approved_account_ids(owner_id, &approved_account_ids);
+            return true;
+        }
+
         // Remove the token from the receiver
-        self.internal_remove_tickets_from_owner(&receiver_id.clone(), &vec![token_id.0]);
+        self.internal_remove_tickets_from_owner(token.raffle_id, &receiver_id, &vec![token_id.0]);
         // Add the token to the original owner
-        self.internal_add_tickets_to_owner(&owner_id, &vec![token_id.0]);
+        self.internal_add_tickets_to_owner(token.raffle_id, &owner_id, &vec![token_id.0]);
 
         // Change the token struct's owner to be the original owner
         token.owner_id = owner_id.clone();
@@ -244,3 +254,121 @@ impl NonFungibleTokenResolver for Contract {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    // Set up an open raffle where user(0) holds ticket 0
+    fn setup_minted_raffle() -> Contract {
+        let mut contract = setup_open_raffle();
+        mint(&mut contract, &user(0), 1);
+
+        contract
+    }
+
+    fn set_transferable(contract: &mut Contract, transferable: bool) {
+        set_caller(&raffle_owner(), 0);
+        contract.set_transferable(U64(RAFFLE_ID), transferable);
+    }
+
+    fn transfer(contract: &mut Contract) {
+        set_caller(&user(0), 1);
+        contract.nft_transfer(user(1), U64(0), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Tickets for this raffle are not transferable")]
+    fn soulbound_tickets_cannot_be_transferred() {
+        let mut contract = setup_minted_raffle();
+        set_transferable(&mut contract, false);
+        transfer(&mut contract);
+    }
+
+    #[test]
+    #[should_panic(expected = "Tickets for this raffle are not transferable")]
+    fn soulbound_tickets_cannot_be_approved() {
+        let mut contract = setup_minted_raffle();
+        set_transferable(&mut contract, false);
+        set_caller(&user(0), STORAGE_DEPOSIT);
+        contract.nft_approve(U64(0), user(1), None);
+    }
+
+    #[test]
+    fn soulbound_tickets_can_still_be_minted_and_burned() {
+        let mut contract = setup_minted_raffle();
+        set_transferable(&mut contract, false);
+        mint(&mut contract, &user(0), 1);
+
+        set_caller(&user(0), 1);
+        contract.nft_burn(U64(0));
+        assert_eq!(contract.nft_supply_for_owner(user(0)).0, 1);
+    }
+
+    #[test]
+    fn tickets_become_transferable_again() {
+        let mut contract = setup_minted_raffle();
+        set_transferable(&mut contract, false);
+        set_transferable(&mut contract, true);
+        transfer(&mut contract);
+        assert_eq!(contract.nft_token(U64(0)).unwrap().owner_id, user(1));
+    }
+
+    fn close(contract: &mut Contract) {
+        set_caller(&raffle_owner(), 0);
+        contract.close_raffle(U64(RAFFLE_ID));
+    }
+
+    #[test]
+    #[should_panic(expected = "Tickets can't be transferred until the raffle is settled")]
+    fn closed_raffles_lock_transfers() {
+        let mut contract = setup_minted_raffle();
+        close(&mut contract);
+        transfer(&mut contract);
+    }
+
+    #[test]
+    #[should_panic(expected = "Tickets can't be transferred until the raffle is settled")]
+    fn drawn_raffles_lock_transfers() {
+        let mut contract = setup_minted_raffle();
+        mint(&mut contract, &user(1), 1);
+        close(&mut contract);
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.draw_winners(U64(RAFFLE_ID), Some(1), None);
+        transfer(&mut contract);
+    }
+
+    #[test]
+    fn settled_raffles_unlock_transfers() {
+        let mut contract = setup_minted_raffle();
+        mint(&mut contract, &user(1), 1);
+        close(&mut contract);
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.draw_winners(U64(RAFFLE_ID), Some(2), None);
+        set_caller(&raffle_owner(), 0);
+        contract.settle_raffle(U64(RAFFLE_ID));
+
+        transfer(&mut contract);
+        assert_eq!(contract.nft_token(U64(0)).unwrap().owner_id, user(1));
+    }
+
+    #[test]
+    fn tickets_are_not_returned_once_the_raffle_closes() {
+        let mut contract = setup_minted_raffle();
+        transfer(&mut contract);
+        close(&mut contract);
+
+        // The receiver asks for the ticket to be returned after the raffle closed
+        set_callback_result(PromiseResult::Successful(b"true".to_vec()));
+        assert!(contract.nft_resolve_transfer(
+            None,
+            user(0),
+            user(1),
+            U64(0),
+            HashMap::new(),
+            None,
+        ));
+        assert_eq!(contract.nft_token(U64(0)).unwrap().owner_id, user(1));
+    }
+}
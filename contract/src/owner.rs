@@ -27,4 +27,29 @@ impl Contract {
         self.assert_contract_owner();
         self.approved_creators.remove(&account_id);
     }
+
+    pub fn is_approved_creator(&self, account_id: AccountId) -> bool {
+        self.approved_creators.contains(&account_id)
+    }
+
+    /// creator limits
+    pub fn set_creator_limits(&mut self, account_id: AccountId, limits: CreatorLimits) {
+        self.assert_contract_owner();
+        self.creator_limits.insert(&account_id, &limits);
+    }
+
+    pub fn remove_creator_limits(&mut self, account_id: AccountId) {
+        self.assert_contract_owner();
+        self.creator_limits.remove(&account_id);
+    }
+
+    pub fn get_creator_limits(&self, account_id: AccountId) -> Option<CreatorLimits> {
+        self.creator_limits.get(&account_id)
+    }
+
+    pub fn get_active_raffles_for_creator(&self, account_id: AccountId) -> u64 {
+        self.active_raffles_per_creator
+            .get(&account_id)
+            .unwrap_or(0)
+    }
 }
@@ -0,0 +1,1126 @@
+use crate::*;
+use near_sdk::{ext_contract, json_types::U64, Gas, PromiseResult};
+
+// Define gas constants for sending out prizes and resolving the transfer
+const GAS_FOR_NFT_PRIZE_TRANSFER: Gas = Gas(15_000_000_000_000);
+const GAS_FOR_FT_PRIZE_TRANSFER: Gas = Gas(15_000_000_000_000);
+const GAS_FOR_RESOLVE_PRIZE_CLAIM: Gas = Gas(10_000_000_000_000);
+
+// External NFT contract that prizes are escrowed from
+#[ext_contract(ext_nft_prize)]
+pub trait NftPrizeContract {
+    fn nft_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: String,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    );
+}
+
+// External fungible token contract that prize pools are funded from
+#[ext_contract(ext_ft_prize)]
+pub trait FtPrizeContract {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+#[near_bindgen]
+impl Contract {
+    // Register an NFT the raffle owner is about to send as a prize with nft_transfer_call.
+    // Only registered NFTs are accepted into escrow, so other contracts can't fake prizes.
+    #[payable]
+    pub fn register_nft_prize(
+        &mut self,
+        raffle_id: U64,
+        nft_contract_id: AccountId,
+        token_id: String,
+    ) {
+        // Measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        self.assert_raffle_owner(&raffle);
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Draft | RaffleStatus::Open | RaffleStatus::Closed
+            ),
+            "Raffle has already been drawn"
+        );
+        require!(
+            nft_contract_id != env::current_account_id(),
+            "Raffle tickets cannot be used as prizes"
+        );
+        let prize = (nft_contract_id, token_id);
+        require!(
+            !raffle.expected_nft_prizes.contains(&prize)
+                && !raffle
+                    .nft_prizes
+                    .iter()
+                    .any(|escrowed| escrowed.nft_contract_id == prize.0
+                        && escrowed.token_id == prize.1),
+            "NFT prize is already registered"
+        );
+
+        raffle.expected_nft_prizes.push(prize);
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+
+        // Refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
+        refund_deposit(env::storage_usage() - initial_storage_usage);
+    }
+
+    // Called by an NFT contract when a raffle owner sends an NFT prize with nft_transfer_call.
+    // The msg must be the ID of the raffle the prize is for, and the NFT must have been registered
+    // with register_nft_prize. Returns false to keep the NFT.
+    pub fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: String,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        let nft_contract_id = env::predecessor_account_id();
+        require!(
+            nft_contract_id != env::current_account_id(),
+            "Raffle tickets cannot be used as prizes"
+        );
+
+        let raffle_id: RaffleId = msg.parse().expect("msg must be a raffle ID");
+        let mut raffle = self.raffle_by_id.get(&raffle_id).expect("Not a raffle");
+        require!(
+            previous_owner_id == raffle.owner_id,
+            "Only the raffle owner can escrow prizes"
+        );
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Draft | RaffleStatus::Open | RaffleStatus::Closed
+            ),
+            "Raffle has already been drawn"
+        );
+        let expected_index = raffle
+            .expected_nft_prizes
+            .iter()
+            .position(|(expected_contract_id, expected_token_id)| {
+                expected_contract_id == &nft_contract_id && expected_token_id == &token_id
+            })
+            .expect("NFT prize was not registered for this raffle");
+        raffle.expected_nft_prizes.remove(expected_index);
+
+        // Hold the NFT in escrow against the raffle
+        raffle.nft_prizes.push(NftPrize {
+            nft_contract_id,
+            token_id,
+            depositor_id: previous_owner_id,
+            claimed: false,
+        });
+        self.raffle_by_id.insert(&raffle_id, &raffle);
+
+        env::log_str(&format!(
+            "NFT prize escrowed for raffle {} by {}",
+            raffle_id, sender_id
+        ));
+
+        PromiseOrValue::Value(false)
+    }
+
+    // Add the attached NEAR to the raffle's prize pool. Only the raffle owner can fund it, since
+    // whatever the winners don't claim is returned to them.
+    #[payable]
+    pub fn fund_raffle(&mut self, raffle_id: U64) {
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        require!(
+            env::predecessor_account_id() == raffle.owner_id,
+            "Only the raffle owner can fund the prize pool"
+        );
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Draft | RaffleStatus::Open | RaffleStatus::Closed
+            ),
+            "Raffle has already been drawn"
+        );
+
+        let amount = env::attached_deposit();
+        require!(amount > 0, "Must attach NEAR to fund the raffle");
+
+        raffle.near_prize_pool += amount;
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+    }
+
+    // Set how the NEAR prize pool is split between winners, in basis points per winner in draw order.
+    // For example [5000, 3000, 2000] gives 50% to the first winner, 30% to the second and 20% to the third.
+    pub fn set_prize_split(&mut self, raffle_id: U64, prize_split: Vec<u32>) {
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        self.assert_raffle_owner(&raffle);
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Draft | RaffleStatus::Open | RaffleStatus::Closed
+            ),
+            "Raffle has already been drawn"
+        );
+        require!(
+            prize_split.iter().sum::<u32>() == 10_000,
+            "Prize split must add up to 10000 basis points"
+        );
+
+        raffle.prize_split = prize_split;
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+    }
+
+    // Set the ordered prize tiers of a raffle. Winners are assigned to the tiers in draw order.
+    #[payable]
+    pub fn set_prize_tiers(&mut self, raffle_id: U64, prize_tiers: Vec<PrizeTier>) {
+        // Measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        self.assert_raffle_owner(&raffle);
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Draft | RaffleStatus::Open | RaffleStatus::Closed
+            ),
+            "Raffle has already been drawn"
+        );
+        for tier in prize_tiers.iter() {
+            require!(tier.quantity > 0, "Prize tier quantity must be at least 1");
+            match tier.asset.as_ref() {
+                Some(PrizeAsset::Nft { token_ids, .. }) => {
+                    require!(
+                        token_ids.len() as u64 == tier.quantity,
+                        "Prize tier must list one NFT per winner"
+                    );
+                }
+                // Tokens paid out by a tier can be sent into the raffle's prize pools,
+                // at least one winner's share at a time
+                Some(PrizeAsset::Ft { token_id, amount }) => {
+                    raffle
+                        .ft_prize_pools
+                        .entry(token_id.clone())
+                        .or_insert_with(|| FtPrizePool::new(amount.0));
+                }
+                _ => {}
+            }
+        }
+
+        raffle.prize_tiers = prize_tiers;
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+
+        // Only charge for storage if the tiers grew. Refund any excess deposit otherwise.
+        let required_storage_in_bytes = env::storage_usage().saturating_sub(initial_storage_usage);
+        refund_deposit(required_storage_in_bytes);
+    }
+
+    // Accept a fungible token into the raffle's prize pools. Tokens from contracts that weren't
+    // registered here or in a prize tier are rejected by ft_on_transfer.
+    // Deposits below min_deposit are rejected too, since every depositor is kept on the raffle.
+    #[payable]
+    pub fn register_prize_token(&mut self, raffle_id: U64, token_id: AccountId, min_deposit: U128) {
+        // Measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        self.assert_raffle_owner(&raffle);
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Draft | RaffleStatus::Open | RaffleStatus::Closed
+            ),
+            "Raffle has already been drawn"
+        );
+        require!(
+            !raffle.ft_prize_pools.contains_key(&token_id),
+            "Token is already registered"
+        );
+        require!(min_deposit.0 > 0, "Minimum deposit must be positive");
+
+        raffle
+            .ft_prize_pools
+            .insert(token_id, FtPrizePool::new(min_deposit.0));
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+
+        // Refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
+        refund_deposit(env::storage_usage() - initial_storage_usage);
+    }
+
+    // Get the ordered prize tiers of a raffle
+    pub fn get_prize_tiers(&self, raffle_id: U64) -> Vec<PrizeTier> {
+        self.raffle_by_id
+            .get(&raffle_id.0)
+            .map(|raffle| raffle.prize_tiers)
+            .unwrap_or_default()
+    }
+
+    // Claim a winning ticket's prize before the claim deadline and send every on-chain
+    // prize it won to the ticket's owner
+    pub fn claim_prize(&mut self, ticket_id: U64) {
+        let ticket = self.ticket_by_id.get(&ticket_id.0).expect("No ticket");
+        require!(
+            env::predecessor_account_id() == ticket.owner_id,
+            "Only the ticket owner can claim its prize"
+        );
+
+        let mut raffle = self
+            .raffle_by_id
+            .get(&ticket.raffle_id)
+            .expect("Not a raffle");
+        let winner_index = claim_winner(&mut raffle, ticket_id);
+        self.raffle_by_id.insert(&ticket.raffle_id, &raffle);
+
+        // Send out each escrowed prize the ticket won that hasn't been sent yet
+        let winner = &raffle.winners[winner_index as usize];
+        if winner.near_prize.0 > 0 && !winner.near_prize_claimed {
+            self.claim_near_prize(ticket_id);
+        }
+        if nft_prize_index(&raffle, winner_index)
+            .is_some_and(|index| !raffle.nft_prizes[index].claimed)
+        {
+            self.claim_nft_prize(ticket_id);
+        }
+        for (token_id, pool) in raffle.ft_prize_pools.iter() {
+            if ft_prize_share(&raffle, winner_index, token_id) > 0
+                && !pool.claimed.contains(&ticket_id)
+            {
+                self.claim_ft_prize(ticket_id, token_id.clone());
+            }
+        }
+    }
+
+    // Send a winning ticket's share of the NEAR prize pool to the ticket's owner
+    pub fn claim_near_prize(&mut self, ticket_id: U64) -> Promise {
+        let ticket = self.ticket_by_id.get(&ticket_id.0).expect("No ticket");
+        require!(
+            env::predecessor_account_id() == ticket.owner_id,
+            "Only the ticket owner can claim its prize"
+        );
+
+        let mut raffle = self
+            .raffle_by_id
+            .get(&ticket.raffle_id)
+            .expect("Not a raffle");
+        let winner_index = claim_winner(&mut raffle, ticket_id);
+        let winner = &mut raffle.winners[winner_index as usize];
+        require!(winner.near_prize.0 > 0, "No NEAR prize for this ticket");
+        require!(!winner.near_prize_claimed, "Prize already claimed");
+
+        // Mark the prize as claimed before sending it so it can't be claimed twice
+        winner.near_prize_claimed = true;
+        let near_prize = winner.near_prize.0;
+        self.raffle_by_id.insert(&ticket.raffle_id, &raffle);
+
+        Promise::new(ticket.owner_id).transfer(near_prize).then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_RESOLVE_PRIZE_CLAIM)
+                .resolve_near_prize_claim(U64(ticket.raffle_id), ticket_id),
+        )
+    }
+
+    // Resolve the transfer from claim_near_prize. If it failed, the prize can be claimed again.
+    #[private]
+    pub fn resolve_near_prize_claim(&mut self, raffle_id: U64, ticket_id: U64) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        if let Some(winner) = raffle
+            .winners
+            .iter_mut()
+            .find(|winner| winner.ticket_id == ticket_id)
+        {
+            winner.near_prize_claimed = false;
+        }
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+
+        false
+    }
+
+    // Called by a fungible token contract when tokens are sent with ft_transfer_call.
+    // The msg is either the ID of the raffle whose prize pool is being funded, or a ticket purchase
+    // such as {"raffle_id": "1", "quantity": 2}. Prize pools only accept registered tokens.
+    // Returns the unused amount.
+    pub fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        let token_id = env::predecessor_account_id();
+
+        if let Ok(purchase) = near_sdk::serde_json::from_str::<FtTicketPurchase>(&msg) {
+            return self.internal_buy_tickets_with_ft(sender_id, token_id, amount, purchase);
+        }
+
+        let raffle_id: RaffleId = msg.parse().expect("msg must be a raffle ID");
+        let mut raffle = self.raffle_by_id.get(&raffle_id).expect("Not a raffle");
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Draft | RaffleStatus::Open | RaffleStatus::Closed
+            ),
+            "Raffle has already been drawn"
+        );
+
+        // Add the tokens to the raffle's pool for this token contract
+        let pool = raffle
+            .ft_prize_pools
+            .get_mut(&token_id)
+            .expect("Token is not registered as a prize for this raffle");
+        require!(
+            amount.0 >= pool.min_deposit.0,
+            format!(
+                "Must deposit at least {} of {}",
+                pool.min_deposit.0, token_id
+            )
+        );
+        pool.amount = U128(pool.amount.0 + amount.0);
+        let deposited = pool.depositors.entry(sender_id.clone()).or_insert(U128(0));
+        *deposited = U128(deposited.0 + amount.0);
+        self.raffle_by_id.insert(&raffle_id, &raffle);
+
+        env::log_str(&format!(
+            "{} of {} added to the prize pool for raffle {} by {}",
+            amount.0, token_id, raffle_id, sender_id
+        ));
+
+        // All of the tokens were used
+        PromiseOrValue::Value(U128(0))
+    }
+
+    // Send a winning ticket's share of a fungible token prize pool to the ticket's owner
+    pub fn claim_ft_prize(&mut self, ticket_id: U64, token_id: AccountId) -> Promise {
+        let ticket = self.ticket_by_id.get(&ticket_id.0).expect("No ticket");
+        require!(
+            env::predecessor_account_id() == ticket.owner_id,
+            "Only the ticket owner can claim its prize"
+        );
+
+        let mut raffle = self
+            .raffle_by_id
+            .get(&ticket.raffle_id)
+            .expect("Not a raffle");
+        let winner_index = claim_winner(&mut raffle, ticket_id);
+        let share = ft_prize_share(&raffle, winner_index, &token_id);
+        require!(share > 0, "No prize from this token for this ticket");
+        let pool = raffle
+            .ft_prize_pools
+            .get_mut(&token_id)
+            .expect("No prize pool for this token");
+        require!(!pool.claimed.contains(&ticket_id), "Prize already claimed");
+
+        // Mark the share as claimed before sending it so it can't be claimed twice
+        pool.claimed.push(ticket_id);
+        self.raffle_by_id.insert(&ticket.raffle_id, &raffle);
+
+        ext_ft_prize::ext(token_id.clone())
+            .with_attached_deposit(1)
+            .with_static_gas(GAS_FOR_FT_PRIZE_TRANSFER)
+            .ft_transfer(
+                ticket.owner_id,
+                U128(share),
+                Some(format!("Raffle {} prize", ticket.raffle_id)),
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_PRIZE_CLAIM)
+                    .resolve_ft_prize_claim(U64(ticket.raffle_id), ticket_id, token_id),
+            )
+    }
+
+    // Resolve the token transfer from claim_ft_prize. If it failed, the share can be claimed again.
+    #[private]
+    pub fn resolve_ft_prize_claim(
+        &mut self,
+        raffle_id: U64,
+        ticket_id: U64,
+        token_id: AccountId,
+    ) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        if let Some(pool) = raffle.ft_prize_pools.get_mut(&token_id) {
+            pool.claimed.retain(|claimed| claimed != &ticket_id);
+        }
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+
+        false
+    }
+
+    // Get the fungible token prize pools held in escrow for a raffle, keyed by token contract
+    pub fn get_ft_prize_pools(&self, raffle_id: U64) -> HashMap<AccountId, FtPrizePool> {
+        self.raffle_by_id
+            .get(&raffle_id.0)
+            .map(|raffle| raffle.ft_prize_pools)
+            .unwrap_or_default()
+    }
+
+    // Send the NFT prize won by a ticket to the ticket's owner
+    pub fn claim_nft_prize(&mut self, ticket_id: U64) -> Promise {
+        let ticket = self.ticket_by_id.get(&ticket_id.0).expect("No ticket");
+        require!(
+            env::predecessor_account_id() == ticket.owner_id,
+            "Only the ticket owner can claim its prize"
+        );
+
+        let mut raffle = self
+            .raffle_by_id
+            .get(&ticket.raffle_id)
+            .expect("Not a raffle");
+        let winner_index = claim_winner(&mut raffle, ticket_id);
+        let prize_index =
+            nft_prize_index(&raffle, winner_index).expect("No NFT prize for this ticket");
+        let prize = &mut raffle.nft_prizes[prize_index];
+        require!(!prize.claimed, "Prize already claimed");
+
+        // Mark the prize as claimed before sending it so it can't be claimed twice
+        prize.claimed = true;
+        let prize = prize.clone();
+        self.raffle_by_id.insert(&ticket.raffle_id, &raffle);
+
+        ext_nft_prize::ext(prize.nft_contract_id)
+            .with_attached_deposit(1)
+            .with_static_gas(GAS_FOR_NFT_PRIZE_TRANSFER)
+            .nft_transfer(ticket.owner_id, prize.token_id, None, None)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_PRIZE_CLAIM)
+                    .resolve_nft_prize_claim(U64(ticket.raffle_id), prize_index as u64),
+            )
+    }
+
+    // Resolve the NFT transfer from claim_nft_prize. If it failed, the prize can be claimed again.
+    #[private]
+    pub fn resolve_nft_prize_claim(&mut self, raffle_id: U64, prize_index: u64) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        raffle.nft_prizes[prize_index as usize].claimed = false;
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+
+        false
+    }
+
+    // Get the NFT prizes held in escrow for a raffle
+    pub fn get_nft_prizes(&self, raffle_id: U64) -> Vec<NftPrize> {
+        self.raffle_by_id
+            .get(&raffle_id.0)
+            .map(|raffle| raffle.nft_prizes)
+            .unwrap_or_default()
+    }
+
+    // Get the registered NFT prizes that haven't been sent into escrow yet, by contract and token ID
+    pub fn get_expected_nft_prizes(&self, raffle_id: U64) -> Vec<(AccountId, String)> {
+        self.raffle_by_id
+            .get(&raffle_id.0)
+            .map(|raffle| raffle.expected_nft_prizes)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::get_created_receipts;
+
+    const PRIZE_POOL: Balance = 1_000;
+
+    // Set up a closed raffle with one ticket for each of two users and a funded NEAR prize pool
+    fn setup_funded_raffle() -> Contract {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), PRIZE_POOL);
+        contract.fund_raffle(U64(RAFFLE_ID));
+        mint(&mut contract, &user(0), 1);
+        mint(&mut contract, &user(1), 1);
+        set_caller(&raffle_owner(), 0);
+        contract.close_raffle(U64(RAFFLE_ID));
+
+        contract
+    }
+
+    fn draw(contract: &mut Contract, num_winners: u64) -> Vec<Winner> {
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.draw_winners(U64(RAFFLE_ID), Some(num_winners), None)
+    }
+
+    #[test]
+    fn near_prize_pool_is_split_evenly_by_default() {
+        let mut contract = setup_funded_raffle();
+        let winners = draw(&mut contract, 2);
+        assert!(winners.iter().all(|winner| winner.near_prize.0 == 500));
+    }
+
+    #[test]
+    fn near_prize_pool_follows_the_prize_split() {
+        let mut contract = setup_funded_raffle();
+        set_caller(&raffle_owner(), 0);
+        contract.set_prize_split(U64(RAFFLE_ID), vec![7_000, 3_000]);
+        let winners = draw(&mut contract, 2);
+        assert_eq!(winners[0].near_prize.0, 700);
+        assert_eq!(winners[1].near_prize.0, 300);
+    }
+
+    #[test]
+    #[should_panic(expected = "Prize split must add up to 10000 basis points")]
+    fn prize_split_must_cover_the_whole_pool() {
+        let mut contract = setup_funded_raffle();
+        set_caller(&raffle_owner(), 0);
+        contract.set_prize_split(U64(RAFFLE_ID), vec![7_000, 2_000]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Number of winners must match the prize split")]
+    fn winners_must_match_the_prize_split() {
+        let mut contract = setup_funded_raffle();
+        set_caller(&raffle_owner(), 0);
+        contract.set_prize_split(U64(RAFFLE_ID), vec![7_000, 3_000]);
+        draw(&mut contract, 1);
+    }
+
+    #[test]
+    fn winners_claim_their_near_prize() {
+        let mut contract = setup_funded_raffle();
+        let winner = draw(&mut contract, 2).remove(0);
+
+        set_caller(&winner.owner_id, 0);
+        contract.claim_near_prize(winner.ticket_id);
+        assert!(raffle(&contract).winners[0].near_prize_claimed);
+
+        // A failed transfer lets the winner claim again
+        set_callback_result(PromiseResult::Failed);
+        assert!(!contract.resolve_near_prize_claim(U64(RAFFLE_ID), winner.ticket_id));
+        assert!(!raffle(&contract).winners[0].near_prize_claimed);
+    }
+
+    #[test]
+    #[should_panic(expected = "Prize already claimed")]
+    fn near_prizes_are_claimed_once() {
+        let mut contract = setup_funded_raffle();
+        let winner = draw(&mut contract, 2).remove(0);
+
+        set_caller(&winner.owner_id, 0);
+        contract.claim_near_prize(winner.ticket_id);
+        contract.claim_near_prize(winner.ticket_id);
+    }
+
+    fn register_nft(contract: &mut Contract, token_id: &str) {
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.register_nft_prize(U64(RAFFLE_ID), nft_contract(), token_id.to_string());
+    }
+
+    fn send_nft(contract: &mut Contract, token_id: &str) {
+        set_caller(&nft_contract(), 0);
+        contract.nft_on_transfer(
+            raffle_owner(),
+            raffle_owner(),
+            token_id.to_string(),
+            RAFFLE_ID.to_string(),
+        );
+    }
+
+    #[test]
+    fn registered_nft_prizes_are_escrowed() {
+        let mut contract = setup_open_raffle();
+        register_nft(&mut contract, "prize");
+        assert_eq!(contract.get_expected_nft_prizes(U64(RAFFLE_ID)).len(), 1);
+
+        send_nft(&mut contract, "prize");
+        assert!(contract.get_expected_nft_prizes(U64(RAFFLE_ID)).is_empty());
+        let nft_prizes = raffle(&contract).nft_prizes;
+        assert_eq!(nft_prizes.len(), 1);
+        assert_eq!(nft_prizes[0].nft_contract_id, nft_contract());
+        assert_eq!(nft_prizes[0].token_id, "prize");
+    }
+
+    #[test]
+    #[should_panic(expected = "NFT prize was not registered for this raffle")]
+    fn unregistered_nft_prizes_are_rejected() {
+        let mut contract = setup_open_raffle();
+        register_nft(&mut contract, "prize");
+        send_nft(&mut contract, "other");
+    }
+
+    #[test]
+    #[should_panic(expected = "NFT prize was not registered for this raffle")]
+    fn registered_nft_prizes_are_escrowed_once() {
+        let mut contract = setup_open_raffle();
+        register_nft(&mut contract, "prize");
+        send_nft(&mut contract, "prize");
+        send_nft(&mut contract, "prize");
+    }
+
+    #[test]
+    #[should_panic(expected = "NFT prize is already registered")]
+    fn nft_prizes_are_registered_once() {
+        let mut contract = setup_open_raffle();
+        register_nft(&mut contract, "prize");
+        register_nft(&mut contract, "prize");
+    }
+
+    fn send_tokens(contract: &mut Contract, token_id: &AccountId, amount: Balance) {
+        set_caller(token_id, 0);
+        contract.ft_on_transfer(user(0), U128(amount), RAFFLE_ID.to_string());
+    }
+
+    #[test]
+    fn registered_tokens_fund_the_prize_pool() {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.register_prize_token(U64(RAFFLE_ID), prize_token(), U128(100));
+        send_tokens(&mut contract, &prize_token(), 300);
+        send_tokens(&mut contract, &prize_token(), 200);
+
+        let pool = &contract.get_ft_prize_pools(U64(RAFFLE_ID))[&prize_token()];
+        assert_eq!(pool.amount.0, 500);
+        assert_eq!(pool.depositors[&user(0)].0, 500);
+    }
+
+    #[test]
+    fn prize_tier_tokens_are_registered() {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.set_prize_tiers(
+            U64(RAFFLE_ID),
+            vec![PrizeTier {
+                title: "Grand Prize".to_string(),
+                media: None,
+                quantity: 1,
+                asset: Some(PrizeAsset::Ft {
+                    token_id: prize_token(),
+                    amount: U128(100),
+                }),
+            }],
+        );
+        send_tokens(&mut contract, &prize_token(), 100);
+
+        let pool = &contract.get_ft_prize_pools(U64(RAFFLE_ID))[&prize_token()];
+        assert_eq!(pool.amount.0, 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is not registered as a prize for this raffle")]
+    fn unregistered_tokens_are_rejected() {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.register_prize_token(U64(RAFFLE_ID), prize_token(), U128(100));
+        send_tokens(&mut contract, &"fake.near".parse().unwrap(), 300);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is already registered")]
+    fn prize_tokens_are_registered_once() {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.register_prize_token(U64(RAFFLE_ID), prize_token(), U128(100));
+        contract.register_prize_token(U64(RAFFLE_ID), prize_token(), U128(100));
+    }
+
+    const CLAIM_DEADLINE: u64 = 1_000;
+
+    fn set_claim_deadline(contract: &mut Contract, claim_deadline: u64) {
+        set_caller(&raffle_owner(), 0);
+        contract.set_claim_deadline(U64(RAFFLE_ID), U64(claim_deadline));
+    }
+
+    fn settle(contract: &mut Contract) {
+        set_caller(&raffle_owner(), 0);
+        contract.settle_raffle(U64(RAFFLE_ID));
+    }
+
+    #[test]
+    #[should_panic(expected = "Winners can still claim their prizes")]
+    fn raffles_cannot_settle_while_prizes_are_unclaimed() {
+        let mut contract = setup_funded_raffle();
+        let winner = draw(&mut contract, 2).remove(0);
+        set_caller(&winner.owner_id, 0);
+        contract.claim_near_prize(winner.ticket_id);
+        settle(&mut contract);
+    }
+
+    #[test]
+    fn raffles_settle_once_every_prize_is_claimed() {
+        let mut contract = setup_funded_raffle();
+        for winner in draw(&mut contract, 2) {
+            set_caller(&winner.owner_id, 0);
+            contract.claim_near_prize(winner.ticket_id);
+        }
+        settle(&mut contract);
+        assert_eq!(
+            contract.get_raffle_status(U64(RAFFLE_ID)),
+            Some(RaffleStatus::Settled)
+        );
+    }
+
+    #[test]
+    fn unclaimed_prizes_return_to_the_owner_after_the_deadline() {
+        let mut contract = setup_funded_raffle();
+        set_claim_deadline(&mut contract, CLAIM_DEADLINE);
+        let winner = draw(&mut contract, 2).remove(0);
+        set_caller(&winner.owner_id, 0);
+        contract.claim_near_prize(winner.ticket_id);
+
+        set_block_timestamp(CLAIM_DEADLINE);
+        settle(&mut contract);
+        set_caller(&raffle_owner(), 0);
+        contract.reclaim_near_prize(U64(RAFFLE_ID));
+        // Only the claimed prize is left in the pool
+        assert_eq!(raffle(&contract).near_prize_pool, 500);
+    }
+
+    #[test]
+    #[should_panic(expected = "Claim deadline can only be extended once the raffle is drawn")]
+    fn claim_deadline_cannot_be_shortened_after_the_draw() {
+        let mut contract = setup_funded_raffle();
+        set_claim_deadline(&mut contract, CLAIM_DEADLINE);
+        draw(&mut contract, 2);
+        set_claim_deadline(&mut contract, CLAIM_DEADLINE - 1);
+    }
+
+    #[test]
+    fn claim_deadline_can_be_added_after_the_draw() {
+        let mut contract = setup_funded_raffle();
+        draw(&mut contract, 2);
+        set_claim_deadline(&mut contract, CLAIM_DEADLINE);
+
+        // Nobody claimed, but the raffle can settle once the new deadline passes
+        set_block_timestamp(CLAIM_DEADLINE);
+        settle(&mut contract);
+        assert_eq!(
+            contract.get_raffle_status(U64(RAFFLE_ID)),
+            Some(RaffleStatus::Settled)
+        );
+    }
+
+    // Set up a raffle drawn with a 70/30 split of the NEAR prize pool and a claim deadline,
+    // where user(0) and user(1) each hold three tickets
+    fn setup_drawn_raffle() -> (Contract, Vec<Winner>) {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), PRIZE_POOL);
+        contract.fund_raffle(U64(RAFFLE_ID));
+        contract.set_prize_split(U64(RAFFLE_ID), vec![7_000, 3_000]);
+        set_claim_deadline(&mut contract, CLAIM_DEADLINE);
+        mint(&mut contract, &user(0), 3);
+        mint(&mut contract, &user(1), 3);
+        set_caller(&raffle_owner(), 0);
+        contract.close_raffle(U64(RAFFLE_ID));
+        let winners = draw(&mut contract, 2);
+
+        (contract, winners)
+    }
+
+    fn claim(contract: &mut Contract, winner: &Winner) {
+        set_caller(&winner.owner_id, 0);
+        contract.claim_prize(winner.ticket_id);
+    }
+
+    fn redraw(contract: &mut Contract) -> Vec<Winner> {
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.redraw_unclaimed(U64(RAFFLE_ID), U64(2 * CLAIM_DEADLINE), None)
+    }
+
+    #[test]
+    fn claiming_a_prize_sends_the_near_prize() {
+        let (mut contract, winners) = setup_drawn_raffle();
+        claim(&mut contract, &winners[0]);
+
+        let claimed = &raffle(&contract).winners[0];
+        assert!(claimed.claimed);
+        assert!(claimed.near_prize_claimed);
+        assert!(!raffle(&contract).winners[1].claimed);
+    }
+
+    #[test]
+    #[should_panic(expected = "Claim deadline has passed")]
+    fn prizes_cannot_be_claimed_after_the_deadline() {
+        let (mut contract, winners) = setup_drawn_raffle();
+        set_block_timestamp(CLAIM_DEADLINE);
+        claim(&mut contract, &winners[0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Ticket did not win")]
+    fn only_winning_tickets_claim_prizes() {
+        let (mut contract, winners) = setup_drawn_raffle();
+        let losing_ticket = (0..6)
+            .map(U64)
+            .find(|ticket_id| winners.iter().all(|winner| winner.ticket_id != *ticket_id))
+            .unwrap();
+        let owner_id = contract.nft_token(losing_ticket).unwrap().owner_id;
+        set_caller(&owner_id, 0);
+        contract.claim_prize(losing_ticket);
+    }
+
+    #[test]
+    fn unclaimed_prizes_are_redrawn() {
+        let (mut contract, winners) = setup_drawn_raffle();
+        claim(&mut contract, &winners[0]);
+        set_block_timestamp(CLAIM_DEADLINE);
+        let new_winners = redraw(&mut contract);
+
+        // Only the unclaimed prize is redrawn, and never to a ticket that already won
+        assert_eq!(new_winners.len(), 1);
+        let new_winner = &new_winners[0];
+        assert!(winners
+            .iter()
+            .all(|winner| winner.ticket_id != new_winner.ticket_id));
+
+        // The new winner takes over the second prize, and the first winner keeps theirs
+        let redrawn = raffle(&contract);
+        assert_eq!(redrawn.winners[0].ticket_id, winners[0].ticket_id);
+        assert_eq!(redrawn.winners[1].ticket_id, new_winner.ticket_id);
+        assert_eq!(redrawn.winners[1].near_prize.0, 300);
+        assert_eq!(redrawn.claim_deadline, Some(2 * CLAIM_DEADLINE));
+
+        // The redraw is recorded as its own round
+        assert_eq!(redrawn.draw_rounds.len(), 2);
+        assert_eq!(redrawn.draw_rounds[1].slots, vec![U64(1)]);
+        assert_eq!(
+            redrawn.draw_rounds[1].ticket_ids,
+            vec![new_winner.ticket_id]
+        );
+
+        // The new winner can claim before the new deadline
+        claim(&mut contract, new_winner);
+        assert!(raffle(&contract).winners[1].near_prize_claimed);
+    }
+
+    #[test]
+    #[should_panic(expected = "Claim deadline has not passed yet")]
+    fn redraws_wait_for_the_claim_deadline() {
+        let (mut contract, _) = setup_drawn_raffle();
+        redraw(&mut contract);
+    }
+
+    #[test]
+    #[should_panic(expected = "Every prize has been claimed")]
+    fn claimed_prizes_are_not_redrawn() {
+        let (mut contract, winners) = setup_drawn_raffle();
+        for winner in winners.iter() {
+            claim(&mut contract, winner);
+        }
+        set_block_timestamp(CLAIM_DEADLINE);
+        redraw(&mut contract);
+    }
+
+    #[test]
+    #[should_panic(expected = "Must deposit at least 100 of usdc.near")]
+    fn prize_pool_deposits_meet_the_minimum() {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.register_prize_token(U64(RAFFLE_ID), prize_token(), U128(100));
+        send_tokens(&mut contract, &prize_token(), 1);
+    }
+
+    // Set up a drawn raffle with two winners sharing a pool of 1000 tokens
+    fn setup_ft_prize_raffle() -> (Contract, Winner) {
+        let mut contract = setup_funded_raffle();
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.register_prize_token(U64(RAFFLE_ID), prize_token(), U128(100));
+        send_tokens(&mut contract, &prize_token(), 1_000);
+        let winner = draw(&mut contract, 2).remove(0);
+
+        (contract, winner)
+    }
+
+    fn ft_prize_claimed(contract: &Contract, ticket_id: U64) -> bool {
+        contract.get_ft_prize_pools(U64(RAFFLE_ID))[&prize_token()]
+            .claimed
+            .contains(&ticket_id)
+    }
+
+    #[test]
+    fn winners_claim_their_ft_prize() {
+        let (mut contract, winner) = setup_ft_prize_raffle();
+        set_caller(&winner.owner_id, 0);
+        contract.claim_ft_prize(winner.ticket_id, prize_token());
+        assert!(ft_prize_claimed(&contract, winner.ticket_id));
+
+        // A failed transfer lets the winner claim again
+        set_callback_result(PromiseResult::Failed);
+        assert!(!contract.resolve_ft_prize_claim(U64(RAFFLE_ID), winner.ticket_id, prize_token()));
+        assert!(!ft_prize_claimed(&contract, winner.ticket_id));
+
+        set_caller(&winner.owner_id, 0);
+        contract.claim_ft_prize(winner.ticket_id, prize_token());
+        assert!(ft_prize_claimed(&contract, winner.ticket_id));
+    }
+
+    #[test]
+    #[should_panic(expected = "Prize already claimed")]
+    fn ft_prizes_are_claimed_once() {
+        let (mut contract, winner) = setup_ft_prize_raffle();
+        set_caller(&winner.owner_id, 0);
+        contract.claim_ft_prize(winner.ticket_id, prize_token());
+        contract.claim_ft_prize(winner.ticket_id, prize_token());
+    }
+
+    fn prize_tiers() -> Vec<PrizeTier> {
+        let tier = |title: &str, quantity, asset| PrizeTier {
+            title: title.to_string(),
+            media: None,
+            quantity,
+            asset: Some(asset),
+        };
+        vec![
+            tier(
+                "Grand Prize",
+                1,
+                PrizeAsset::Nft {
+                    nft_contract_id: nft_contract(),
+                    token_ids: vec!["grand".to_string()],
+                },
+            ),
+            tier("Runner-up", 2, PrizeAsset::Near { amount: U128(300) }),
+            tier(
+                "Gift Card",
+                1,
+                PrizeAsset::Ft {
+                    token_id: prize_token(),
+                    amount: U128(50),
+                },
+            ),
+        ]
+    }
+
+    // Set up a closed raffle with four tickets and the prize tiers above, escrowing the grand
+    // prize NFT if asked and funding the NEAR and token pools with the given amounts
+    fn setup_tiered_raffle(escrow_nft: bool, near_pool: Balance, ft_pool: Balance) -> Contract {
+        let mut contract = setup_open_raffle();
+        mint(&mut contract, &user(0), 2);
+        mint(&mut contract, &user(1), 2);
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.set_prize_tiers(U64(RAFFLE_ID), prize_tiers());
+
+        set_caller(&raffle_owner(), near_pool);
+        contract.fund_raffle(U64(RAFFLE_ID));
+        if escrow_nft {
+            register_nft(&mut contract, "grand");
+            send_nft(&mut contract, "grand");
+        }
+        if ft_pool > 0 {
+            send_tokens(&mut contract, &prize_token(), ft_pool);
+        }
+        set_caller(&raffle_owner(), 0);
+        contract.close_raffle(U64(RAFFLE_ID));
+
+        contract
+    }
+
+    #[test]
+    fn winners_are_assigned_to_tiers_in_draw_order() {
+        let mut contract = setup_tiered_raffle(true, 600, 50);
+        let winners = draw(&mut contract, 4);
+
+        let tiers: Vec<Option<u64>> = winners.iter().map(|winner| winner.prize_tier).collect();
+        assert_eq!(tiers, vec![Some(0), Some(1), Some(1), Some(2)]);
+        let near_prizes: Vec<Balance> = winners.iter().map(|winner| winner.near_prize.0).collect();
+        assert_eq!(near_prizes, vec![0, 300, 300, 0]);
+
+        let raffle = raffle(&contract);
+        assert_eq!(nft_prize_index(&raffle, 0), Some(0));
+        assert_eq!(nft_prize_index(&raffle, 1), None);
+        let ft_prizes: Vec<Balance> = (0..4)
+            .map(|winner_index| ft_prize_share(&raffle, winner_index, &prize_token()))
+            .collect();
+        assert_eq!(ft_prizes, vec![0, 0, 0, 50]);
+    }
+
+    #[test]
+    #[should_panic(expected = "NFT grand from nft.near is not in escrow")]
+    fn tiers_need_their_nfts_in_escrow() {
+        let mut contract = setup_tiered_raffle(false, 600, 50);
+        draw(&mut contract, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "NEAR prize pool is too small for the prize tiers")]
+    fn tiers_need_enough_near_in_the_pool() {
+        let mut contract = setup_tiered_raffle(true, 599, 50);
+        draw(&mut contract, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "usdc.near prize pool is too small for the prize tiers")]
+    fn tiers_need_enough_tokens_in_the_pool() {
+        let mut contract = setup_tiered_raffle(true, 600, 0);
+        draw(&mut contract, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "Number of winners must match the prize tiers")]
+    fn winners_must_match_the_prize_tiers() {
+        let mut contract = setup_tiered_raffle(true, 600, 50);
+        draw(&mut contract, 3);
+    }
+
+    // Set up a drawn raffle where the first winner wins an escrowed NFT
+    fn setup_nft_prize_raffle() -> (Contract, Winner) {
+        let mut contract = setup_funded_raffle();
+        register_nft(&mut contract, "prize");
+        send_nft(&mut contract, "prize");
+        let winner = draw(&mut contract, 2).remove(0);
+
+        (contract, winner)
+    }
+
+    fn nft_prize_claimed(contract: &Contract) -> bool {
+        raffle(contract).nft_prizes[0].claimed
+    }
+
+    #[test]
+    fn winners_receive_their_nft_prize() {
+        let (mut contract, winner) = setup_nft_prize_raffle();
+        set_caller(&winner.owner_id, 0);
+        contract.claim_nft_prize(winner.ticket_id);
+        assert!(nft_prize_claimed(&contract));
+
+        // The NFT contract is asked to send the prize to the winner
+        let receipt = get_created_receipts()
+            .into_iter()
+            .find(|receipt| receipt.receiver_id == nft_contract())
+            .unwrap();
+        let VmAction::FunctionCall {
+            function_name,
+            args,
+            ..
+        } = &receipt.actions[0]
+        else {
+            panic!("Expected a function call");
+        };
+        assert_eq!(function_name, "nft_transfer");
+        let args: near_sdk::serde_json::Value = near_sdk::serde_json::from_slice(args).unwrap();
+        assert_eq!(args["receiver_id"], winner.owner_id.to_string());
+        assert_eq!(args["token_id"], "prize");
+    }
+
+    #[test]
+    fn failed_nft_prize_transfers_can_be_claimed_again() {
+        let (mut contract, winner) = setup_nft_prize_raffle();
+        set_caller(&winner.owner_id, 0);
+        contract.claim_nft_prize(winner.ticket_id);
+
+        set_callback_result(PromiseResult::Failed);
+        assert!(!contract.resolve_nft_prize_claim(U64(RAFFLE_ID), 0));
+        assert!(!nft_prize_claimed(&contract));
+
+        set_caller(&winner.owner_id, 0);
+        contract.claim_nft_prize(winner.ticket_id);
+        assert!(nft_prize_claimed(&contract));
+    }
+
+    #[test]
+    #[should_panic(expected = "Prize already claimed")]
+    fn nft_prizes_are_claimed_once() {
+        let (mut contract, winner) = setup_nft_prize_raffle();
+        set_caller(&winner.owner_id, 0);
+        contract.claim_nft_prize(winner.ticket_id);
+        contract.claim_nft_prize(winner.ticket_id);
+    }
+}
@@ -1,16 +1,16 @@
+// create_raffle takes a lot of optional configuration. The allow has to be module-wide since
+// near_bindgen generates a matching cross-contract helper that doesn't carry method attributes.
+#![allow(clippy::too_many_arguments)]
+
 use near_sdk::json_types::U64;
+use near_sdk::Gas;
 
 use crate::*;
 
-/// Injected Keypom Args struct to be sent to external contracts
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(crate = "near_sdk::serde")]
-pub struct KeypomArgs {
-    pub account_id_field: Option<String>,
-    pub funder_id_field: Option<String>,
-    pub drop_id_field: Option<String>,
-    pub key_id_field: Option<String>
-}
+// Rough gas cost of a batch mint, used to reject batches that can't finish
+const GAS_FOR_MINT_BATCH: Gas = Gas(10_000_000_000_000);
+const GAS_PER_BATCH_RECEIVER: Gas = Gas(1_000_000_000_000);
+const GAS_PER_BATCH_TICKET: Gas = Gas(1_000_000_000_000);
 
 #[near_bindgen]
 impl Contract {
@@ -21,12 +21,65 @@ impl Contract {
         raffle_metadata: RaffleMetadata,
         funder_id: Option<AccountId>,
         drop_id: Option<String>,
+        tickets_per_claim: Option<u64>,
         royalty: Option<HashMap<AccountId, u32>>,
+        starts_at: Option<U64>,
+        ends_at: Option<U64>,
+        num_winners: Option<u64>,
     ) {
         //measure the initial storage being used on the contract
         let initial_storage_usage = env::storage_usage();
 
         let caller = env::predecessor_account_id();
+        assert!(
+            self.approved_creators.contains(&caller),
+            "Not approved creator"
+        );
+
+        //make sure the creator stays within the limits the contract owner set for them
+        let limits = self.creator_limits.get(&caller).unwrap_or_default();
+        let active_raffles = self.active_raffles_per_creator.get(&caller).unwrap_or(0);
+        if let Some(max_active_raffles) = limits.max_active_raffles {
+            require!(
+                active_raffles < max_active_raffles,
+                "Creator has too many active raffles"
+            );
+        }
+        if let Some(max_tickets_per_raffle) = limits.max_tickets_per_raffle {
+            require!(
+                raffle_metadata
+                    .max_tickets
+                    .is_some_and(|max_tickets| max_tickets <= max_tickets_per_raffle),
+                &format!(
+                    "max_tickets must be set and at most {}",
+                    max_tickets_per_raffle
+                )
+            );
+        }
+        self.active_raffles_per_creator
+            .insert(&caller, &(active_raffles + 1));
+
+        //make sure the raffle window (if any) isn't empty
+        if let (Some(starts_at), Some(ends_at)) = (starts_at, ends_at) {
+            require!(starts_at.0 < ends_at.0, "starts_at must be before ends_at");
+        }
+        //the Keypom drop the raffle starts with goes on its allowlist, so it needs both IDs
+        require!(
+            funder_id.is_some() == drop_id.is_some(),
+            "funder_id and drop_id must be passed together"
+        );
+        require!(
+            tickets_per_claim.is_none() || drop_id.is_some(),
+            "tickets_per_claim can only be passed along with a drop"
+        );
+        require!(
+            tickets_per_claim.is_none_or(|tickets_per_claim| tickets_per_claim > 0),
+            "tickets_per_claim must be positive"
+        );
+        require!(
+            num_winners.is_none_or(|num_winners| num_winners > 0),
+            "Must draw at least one winner"
+        );
 
         require!(
             self.raffle_by_id
@@ -43,15 +96,40 @@ impl Contract {
                 .insert(
                     &raffle_id,
                     &Raffle {
-                        funder_id,
-                        drop_id,
-                        metadata: raffle_metadata,
+                        keypom_drops: initial_keypom_drops(funder_id, drop_id, tickets_per_claim),
+                        //no tickets have been minted yet, whatever the caller passed in
+                        metadata: RaffleMetadata {
+                            live_tickets: 0,
+                            ..raffle_metadata
+                        },
                         //we add an optional parameter for perpetual royalties
                         royalty,
+                        transferable: true,
                         tickets: UnorderedSet::new(StorageKey::RaffleTickets {
                             // We get a new unique prefix for the collection
                             raffle_id_hash: hash_account_id(&format!("{}{}", raffle_id, caller)),
                         }),
+                        status: RaffleStatus::Draft,
+                        starts_at: starts_at.map(|t| t.0),
+                        ends_at: ends_at.map(|t| t.0),
+                        num_winners,
+                        draw_commitment: None,
+                        draw_rounds: vec![],
+                        claim_deadline: None,
+                        prize_tiers: vec![],
+                        winners: vec![],
+                        nft_prizes: vec![],
+                        expected_nft_prizes: vec![],
+                        near_prize_pool: 0,
+                        prize_split: vec![],
+                        ft_prize_pools: HashMap::new(),
+                        ticket_price: None,
+                        ft_ticket_price: None,
+                        beneficiary_id: None,
+                        charity_split: HashMap::new(),
+                        near_proceeds: 0,
+                        ft_proceeds: HashMap::new(),
+                        unpaid_proceeds: vec![],
                         owner_id: caller
                     }
                 )
@@ -69,108 +147,977 @@ impl Contract {
         refund_deposit(required_storage_in_bytes);
     }
 
+    // Mint tickets to the receiver. Keypom calls this with its injected keypom_args along with the
+    // drop, funder and key that are claiming, and the drop's tickets_per_claim is minted instead of
+    // ticket_amount if it has one. Other approved minters leave all of those out.
     #[payable]
     pub fn mint_ticket(
         &mut self,
         raffle_id: U64,
         receiver_id: AccountId,
         ticket_amount: u64,
-        drop_id: String,
-        funder_id: AccountId,
-        keypom_args: KeypomArgs,
+        drop_id: Option<KeypomId>,
+        funder_id: Option<AccountId>,
+        key_id: Option<KeypomId>,
+        keypom_args: Option<KeypomArgs>,
     ) {
-        // Ensure the injected keypom args are not malicious
-        require!(
-            keypom_args.funder_id_field.unwrap() == "funder_id".to_string(),
-            "Malicious call. Injected keypom args don't match"
-        );
-        require!(
-            keypom_args.drop_id_field.unwrap() == "drop_id".to_string(),
-            "Malicious call. Injected keypom args don't match"
+        // Measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        let predecessor = env::predecessor_account_id();
+        assert!(
+            self.approved_minters.contains(&predecessor),
+            "Not approved minter"
         );
-        require!(
-            keypom_args.account_id_field.unwrap() == "receiver_id".to_string(),
-            "Malicious call. Injected keypom args don't match"
+
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        // Ensure the injected keypom args are not malicious and the drop can mint into this raffle
+        let keypom_key = keypom_key_from_args(drop_id, funder_id, key_id, keypom_args);
+        let ticket_amount = match keypom_key.as_ref() {
+            Some(keypom_key) => {
+                self.internal_use_keypom_key(raffle_id.0, keypom_key);
+                keypom_ticket_amount(&mut raffle, keypom_key, ticket_amount)
+            }
+            None => {
+                require!(ticket_amount > 0, "Must mint at least one ticket");
+                ticket_amount
+            }
+        };
+
+        let tickets = self.internal_mint_tickets(
+            raffle_id.0,
+            &mut raffle,
+            &receiver_id,
+            ticket_amount,
+            Some(&predecessor),
         );
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
 
+        // Remember which Keypom key each ticket was minted with
+        if let Some(keypom_key) = keypom_key {
+            for ticket_id in tickets.iter() {
+                self.keypom_key_by_ticket.insert(ticket_id, &keypom_key);
+            }
+        }
+        log_nft_mint(&[(receiver_id, tickets)]);
+
+        // Calculate the required storage which was used - initial
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+
+        // Refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
+        refund_deposit(required_storage_in_bytes);
+    }
+
+    // Mint tickets to many receivers at once, e.g. to airdrop entries to registered attendees.
+    // Receivers listed more than once get all of their tickets together. The attached deposit
+    // must cover the storage for the whole batch.
+    #[payable]
+    pub fn mint_tickets_batch(&mut self, raffle_id: U64, receivers: Vec<(AccountId, u64)>) {
         // Measure the initial storage being used on the contract
         let initial_storage_usage = env::storage_usage();
 
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
         let predecessor = env::predecessor_account_id();
         assert!(
-            self.approved_minters.contains(&predecessor),
+            self.approved_minters.contains(&predecessor)
+                || raffle.owner_id == predecessor
+                || self.owner_id == predecessor,
             "Not approved minter"
         );
 
+        // Merge repeated receivers, keeping the order they were first listed in
+        let mut batch: Vec<(AccountId, u64)> = vec![];
+        for (receiver_id, ticket_amount) in receivers {
+            require!(
+                ticket_amount > 0,
+                "Must mint at least one ticket per receiver"
+            );
+            match batch
+                .iter_mut()
+                .find(|(account_id, _)| account_id == &receiver_id)
+            {
+                Some((_, total)) => {
+                    *total = total
+                        .checked_add(ticket_amount)
+                        .expect("Too many tickets in the batch. Split it into smaller batches")
+                }
+                None => batch.push((receiver_id, ticket_amount)),
+            }
+        }
+        require!(!batch.is_empty(), "No receivers to mint to");
+
+        // Refuse batches that would run out of gas part way through
+        let total_tickets = batch.iter().try_fold(0u64, |total, (_, ticket_amount)| {
+            total.checked_add(*ticket_amount)
+        });
+        let projected_gas = total_tickets
+            .and_then(|total_tickets| GAS_PER_BATCH_TICKET.0.checked_mul(total_tickets))
+            .and_then(|gas| {
+                gas.checked_add(GAS_PER_BATCH_RECEIVER.0.saturating_mul(batch.len() as u64))
+            })
+            .and_then(|gas| gas.checked_add(GAS_FOR_MINT_BATCH.0))
+            .expect("Too many tickets in the batch. Split it into smaller batches");
+        let available_gas = env::prepaid_gas().0 - env::used_gas().0;
+        require!(
+            projected_gas <= available_gas,
+            format!(
+                "Batch needs about {} gas but only {} is available. Split it into smaller batches",
+                projected_gas, available_gas
+            )
+        );
+
+        let mut minted = vec![];
+        for (receiver_id, ticket_amount) in batch {
+            let tickets = self.internal_mint_tickets(
+                raffle_id.0,
+                &mut raffle,
+                &receiver_id,
+                ticket_amount,
+                Some(&predecessor),
+            );
+            minted.push((receiver_id, tickets));
+        }
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+        log_nft_mint(&minted);
+
+        // Calculate the required storage which was used - initial
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+
+        // Refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
+        refund_deposit(required_storage_in_bytes);
+    }
+
+    #[payable]
+    pub fn draw_winners(
+        &mut self,
+        raffle_id: U64,
+        num_winners: Option<u64>,
+        secret: Option<Base64VecU8>,
+    ) -> Vec<Winner> {
+        // Measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
         let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        let predecessor = env::predecessor_account_id();
+        let num_winners = if raffle.owner_id == predecessor || self.owner_id == predecessor {
+            // The owner can pick the number of winners, unless it was fixed when the raffle was created
+            let num_winners = num_winners
+                .or(raffle.num_winners)
+                .expect("Must pass the number of winners to draw");
+            require!(
+                raffle
+                    .num_winners
+                    .is_none_or(|configured| configured == num_winners),
+                "Number of winners must match the raffle's configuration"
+            );
+            num_winners
+        } else {
+            // Once the raffle's end time has passed, anyone can trigger the draw with the
+            // number of winners set when the raffle was created
+            require!(
+                raffle
+                    .ends_at
+                    .is_some_and(|ends_at| env::block_timestamp() >= ends_at),
+                "only raffle owner"
+            );
+            raffle.num_winners.expect(
+                "Only the raffle owner can draw a raffle without a configured number of winners",
+            )
+        };
+
         require!(
-            raffle.drop_id.as_ref() == Some(drop_id).as_ref(),
-            "drop_id mismatch"
+            raffle_status(&raffle) == RaffleStatus::Closed,
+            "Raffle must be closed before drawing"
         );
+        require!(num_winners > 0, "Must draw at least one winner");
         require!(
-            raffle.funder_id.as_ref() == Some(funder_id).as_ref(),
-            "funder_id mismatch"
+            num_winners <= raffle.tickets.len(),
+            "Cannot draw more winners than there are tickets"
         );
-
-        let cur_len = raffle.tickets.len() - 1 + ticket_amount;
-        // Ensure we haven't overflowed on the number of copies minted
-        if let Some(max) = raffle.metadata.max_tickets {
+        require!(
+            raffle.prize_split.is_empty() || raffle.prize_split.len() as u64 == num_winners,
+            "Number of winners must match the prize split"
+        );
+        if !raffle.prize_tiers.is_empty() {
+            let num_prizes: u64 = raffle.prize_tiers.iter().map(|tier| tier.quantity).sum();
             require!(
-                cur_len < max,
-                "Cannot mint any more tickets for the given raffle. Limit reached"
+                num_prizes == num_winners,
+                "Number of winners must match the prize tiers"
             );
+            assert_prize_tiers_escrowed(&raffle);
         }
 
-        let mut tickets = vec![];
-        for _ in 0..ticket_amount {
-            let ticket_id = raffle.tickets.len();
-            tickets.push(ticket_id);
-
-            raffle.tickets.insert(&ticket_id);
-            // Specify the ticket struct that contains the owner ID
-            let ticket = Ticket {
-                // Series ID that the ticket belongs to
-                raffle_id: raffle_id.0,
-                // Set the owner ID equal to the receiver ID passed into the function
-                owner_id: receiver_id.clone(),
-                // Set the approved account IDs to the default value (an empty map)
-                approved_account_ids: Default::default(),
-                // The next approval ID is set to 0
-                next_approval_id: 0,
-            };
+        // Pick the winning tickets and assign them to prize tiers and their share of the NEAR pool
+        let mut winners = self.internal_draw_round(&mut raffle, (0..num_winners).collect(), secret);
+        for (index, (winner, near_prize)) in winners
+            .iter_mut()
+            .zip(near_prize_payouts(&raffle, num_winners))
+            .enumerate()
+        {
+            winner.prize_tier = prize_tier_for_winner(&raffle, index as u64).map(|(tier, _)| tier);
+            winner.near_prize = near_prize;
+        }
+        raffle.winners = winners.clone();
+        raffle.status = RaffleStatus::Drawn;
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
 
-            require!(
-                self.ticket_by_id.insert(&ticket_id, &ticket).is_none(),
-                "Ticket already exists"
-            );
+        log_raffle_draw(raffle_id.0, &winners);
+
+        // Calculate the required storage which was used - initial
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+
+        // Refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
+        refund_deposit(required_storage_in_bytes);
 
-            // Construct the mint log as per the events standard.
-            let nft_mint_log: EventLog = EventLog {
-                // Standard name ("nep171")
-                standard: NFT_STANDARD_NAME.to_string(),
-                // Version of the standard ("nft-1.0.0")
-                version: NFT_METADATA_SPEC.to_string(),
-                // The data related with the event stored in a vector
-                event: EventLogVariant::NftMint(vec![NftMintLog {
-                    // Owner of the ticket
-                    owner_id: receiver_id.to_string(),
-                    // Vector of ticket IDs that were minted
-                    token_ids: vec![ticket_id.to_string()],
-                    // An optional memo to include
-                    memo: None,
-                }]),
-            };
-
-            // Log the serialized JSON
-            env::log_str(&nft_mint_log.to_string());
+        winners
+    }
+
+    // Redraw every prize that wasn't claimed before the claim deadline. Tickets that were already
+    // drawn can't win again. The new winners have until the passed-in deadline to claim.
+    #[payable]
+    pub fn redraw_unclaimed(
+        &mut self,
+        raffle_id: U64,
+        claim_deadline: U64,
+        secret: Option<Base64VecU8>,
+    ) -> Vec<Winner> {
+        // Measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        self.assert_raffle_owner(&raffle);
+
+        require!(
+            raffle_status(&raffle) == RaffleStatus::Drawn,
+            "Raffle must be drawn before redrawing"
+        );
+        require!(
+            raffle
+                .claim_deadline
+                .is_some_and(|deadline| env::block_timestamp() >= deadline),
+            "Claim deadline has not passed yet"
+        );
+        require!(
+            claim_deadline.0 > env::block_timestamp(),
+            "Claim deadline must be in the future"
+        );
+
+        // Find the prizes nobody claimed
+        let slots: Vec<u64> = raffle
+            .winners
+            .iter()
+            .enumerate()
+            .filter(|(_, winner)| !winner.claimed)
+            .map(|(index, _)| index as u64)
+            .collect();
+        require!(!slots.is_empty(), "Every prize has been claimed");
+
+        // The new winners take over the prize of the winner they replace
+        let new_winners = self.internal_draw_round(&mut raffle, slots.clone(), secret);
+        for (slot, new_winner) in slots.iter().zip(new_winners.iter()) {
+            let winner = &mut raffle.winners[*slot as usize];
+            winner.ticket_id = new_winner.ticket_id;
+            winner.owner_id = new_winner.owner_id.clone();
+            winner.near_prize_claimed = false;
         }
-        self.internal_add_tickets_to_owner(&receiver_id, &tickets);
+        raffle.claim_deadline = Some(claim_deadline.0);
         self.raffle_by_id.insert(&raffle_id.0, &raffle);
 
+        log_raffle_draw(raffle_id.0, &new_winners);
+
         // Calculate the required storage which was used - initial
         let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
 
         // Refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
         refund_deposit(required_storage_in_bytes);
+
+        new_winners
+    }
+
+    // Set the time (in nanoseconds) by which winners must claim their prize
+    pub fn set_claim_deadline(&mut self, raffle_id: U64, claim_deadline: U64) {
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        self.assert_raffle_owner(&raffle);
+        require!(
+            !matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Settled | RaffleStatus::Cancelled
+            ),
+            "Raffle is already finished"
+        );
+        require!(
+            claim_deadline.0 > env::block_timestamp(),
+            "Claim deadline must be in the future"
+        );
+        // Winners are counting on the deadline once they're drawn, so it can only be pushed back.
+        // A raffle drawn without one can still get a deadline so no-shows don't block settling.
+        if raffle_status(&raffle) == RaffleStatus::Drawn {
+            require!(
+                raffle
+                    .claim_deadline
+                    .is_none_or(|deadline| claim_deadline.0 > deadline),
+                "Claim deadline can only be extended once the raffle is drawn"
+            );
+        }
+
+        raffle.claim_deadline = Some(claim_deadline.0);
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+    }
+
+    // Commit to the sha256 hash of a secret that has to be revealed for the next draw round.
+    // Only the owner knows the secret, so raffles anyone can draw once they end (those created
+    // with both ends_at and num_winners) can't use commit-reveal.
+    pub fn commit_draw_secret(&mut self, raffle_id: U64, secret_hash: Base64VecU8) {
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        self.assert_raffle_owner(&raffle);
+        require!(
+            raffle.ends_at.is_none() || raffle.num_winners.is_none(),
+            "Raffles anyone can draw can't use commit-reveal"
+        );
+
+        require!(
+            raffle.draw_commitment.is_none(),
+            "Draw secret already committed"
+        );
+        require!(
+            !matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Settled | RaffleStatus::Cancelled
+            ),
+            "Raffle is already finished"
+        );
+        require!(
+            secret_hash.0.len() == 32,
+            "Secret hash must be a sha256 hash"
+        );
+
+        raffle.draw_commitment = Some(secret_hash.0);
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+    }
+
+    // Get the inputs that were used for every draw round of a raffle, starting with the initial draw
+    pub fn get_draw_rounds(&self, raffle_id: U64) -> Vec<DrawTranscript> {
+        self.raffle_by_id
+            .get(&raffle_id.0)
+            .map(|raffle| raffle.draw_rounds)
+            .unwrap_or_default()
+    }
+
+    // Re-derive the seed and ticket positions drawn in a round (the initial draw by default) from
+    // its transcript's block seed and revealed secret, and return the ticket IDs recorded at those
+    // positions when it was drawn
+    pub fn derive_winners(&self, raffle_id: U64, round: Option<u64>) -> Vec<U64> {
+        let raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        let round = round.unwrap_or(0) as usize;
+        let transcript = raffle.draw_rounds.get(round).expect("No such draw round");
+
+        // The revealed secret has to match the commitment made before the draw
+        let secret = transcript.commitment.as_ref().map(|commitment| {
+            let secret = transcript
+                .secret
+                .as_ref()
+                .expect("Draw round is missing its revealed secret");
+            require!(
+                env::sha256(&secret.0) == commitment.0,
+                "Revealed secret does not match the commitment"
+            );
+            secret.0.as_slice()
+        });
+        require!(
+            draw_seed(&transcript.block_seed.0, secret) == transcript.seed.0,
+            "Draw seed does not match its transcript"
+        );
+
+        // Tickets drawn in earlier rounds were excluded from this one
+        let excluded: Vec<u64> = raffle.draw_rounds[..round]
+            .iter()
+            .flat_map(|round| round.indices.iter().map(|index| index.0))
+            .collect();
+
+        let indices: Vec<U64> = draw_indices(
+            &transcript.seed.0,
+            transcript.num_tickets.0,
+            transcript.num_winners.0,
+            &excluded,
+        )
+        .into_iter()
+        .map(U64)
+        .collect();
+        require!(
+            indices == transcript.indices,
+            "Draw round does not match its transcript"
+        );
+
+        // Burned tickets are swapped out of the ticket list, so use the IDs recorded at draw time
+        transcript.ticket_ids.clone()
+    }
+
+    // Allow or stop ticket owners from transferring and approving their tickets.
+    // Minting and burning are unaffected.
+    pub fn set_transferable(&mut self, raffle_id: U64, transferable: bool) {
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        self.assert_raffle_owner(&raffle);
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Draft | RaffleStatus::Open
+            ),
+            "Raffle is no longer accepting tickets"
+        );
+
+        raffle.transferable = transferable;
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+    }
+
+    // Start accepting tickets for a draft raffle
+    pub fn open_raffle(&mut self, raffle_id: U64) {
+        self.internal_update_raffle_status(raffle_id.0, RaffleStatus::Draft, RaffleStatus::Open);
+    }
+
+    // Freeze ticket sales so the raffle can be drawn
+    pub fn close_raffle(&mut self, raffle_id: U64) {
+        self.internal_update_raffle_status(raffle_id.0, RaffleStatus::Open, RaffleStatus::Closed);
+    }
+
+    // Mark a drawn raffle as finished once every prize has been claimed or the claim deadline has
+    // passed. Prizes nobody claimed can then be reclaimed by whoever put them up.
+    pub fn settle_raffle(&mut self, raffle_id: U64) {
+        // Winners keep their prizes until they claim them or the claim deadline passes
+        let raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        let deadline_passed = raffle
+            .claim_deadline
+            .is_some_and(|deadline| env::block_timestamp() >= deadline);
+        require!(
+            deadline_passed
+                || (0..raffle.winners.len() as u64)
+                    .all(|winner_index| winner_prizes_claimed(&raffle, winner_index)),
+            "Winners can still claim their prizes"
+        );
+
+        self.internal_update_raffle_status(raffle_id.0, RaffleStatus::Drawn, RaffleStatus::Settled);
+
+        // Settled raffles no longer count towards the creator's active raffles
+        let raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        self.internal_remove_active_raffle(&raffle.owner_id);
+    }
+
+    // Get the current lifecycle state of a raffle
+    pub fn get_raffle_status(&self, raffle_id: U64) -> Option<RaffleStatus> {
+        self.raffle_by_id
+            .get(&raffle_id.0)
+            .map(|raffle| raffle_status(&raffle))
+    }
+
+    // Get the winning tickets that were drawn for a raffle
+    pub fn get_winners(&self, raffle_id: U64) -> Vec<Winner> {
+        self.raffle_by_id
+            .get(&raffle_id.0)
+            .map(|raffle| raffle.winners)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::test_utils::get_logs;
+
+    fn status(contract: &Contract) -> RaffleStatus {
+        contract.get_raffle_status(U64(RAFFLE_ID)).unwrap()
+    }
+
+    #[test]
+    fn raffle_moves_through_its_lifecycle() {
+        let mut contract = setup_draft_raffle(raffle_metadata(None));
+        assert_eq!(status(&contract), RaffleStatus::Draft);
+
+        set_caller(&raffle_owner(), 0);
+        contract.open_raffle(U64(RAFFLE_ID));
+        assert_eq!(status(&contract), RaffleStatus::Open);
+
+        mint(&mut contract, &user(0), 2);
+        set_caller(&raffle_owner(), 0);
+        contract.close_raffle(U64(RAFFLE_ID));
+        assert_eq!(status(&contract), RaffleStatus::Closed);
+
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.draw_winners(U64(RAFFLE_ID), Some(1), None);
+        assert_eq!(status(&contract), RaffleStatus::Drawn);
+
+        set_caller(&raffle_owner(), 0);
+        contract.settle_raffle(U64(RAFFLE_ID));
+        assert_eq!(status(&contract), RaffleStatus::Settled);
+        assert_eq!(contract.get_active_raffles_for_creator(raffle_owner()), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Raffle is not open for minting")]
+    fn draft_raffles_cannot_mint() {
+        let mut contract = setup_draft_raffle(raffle_metadata(None));
+        mint(&mut contract, &user(0), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Raffle must be Open, but is Draft")]
+    fn raffles_cannot_skip_a_state() {
+        let mut contract = setup_draft_raffle(raffle_metadata(None));
+        set_caller(&raffle_owner(), 0);
+        contract.close_raffle(U64(RAFFLE_ID));
+    }
+
+    #[test]
+    #[should_panic(expected = "Raffle must be closed before drawing")]
+    fn open_raffles_cannot_be_drawn() {
+        let mut contract = setup_open_raffle();
+        mint(&mut contract, &user(0), 2);
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.draw_winners(U64(RAFFLE_ID), Some(1), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "only raffle owner")]
+    fn only_the_owner_moves_the_raffle_forward() {
+        let mut contract = setup_draft_raffle(raffle_metadata(None));
+        set_caller(&user(0), 0);
+        contract.open_raffle(U64(RAFFLE_ID));
+    }
+
+    const STARTS_AT: u64 = 1_000;
+    const ENDS_AT: u64 = 2_000;
+
+    // Set up a raffle that is open from STARTS_AT until ENDS_AT, with three tickets minted
+    fn setup_timed_raffle(num_winners: Option<u64>) -> Contract {
+        let mut contract = setup_contract();
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.create_raffle(
+            RAFFLE_ID,
+            raffle_metadata(None),
+            None,
+            None,
+            None,
+            None,
+            Some(U64(STARTS_AT)),
+            Some(U64(ENDS_AT)),
+            num_winners,
+        );
+        set_block_timestamp(STARTS_AT);
+        mint(&mut contract, &user(0), 3);
+
+        contract
+    }
+
+    #[test]
+    fn timed_raffles_open_and_close_on_schedule() {
+        let mut contract = setup_timed_raffle(None);
+        set_block_timestamp(STARTS_AT - 1);
+        set_caller(&user(0), 0);
+        assert_eq!(status(&contract), RaffleStatus::Draft);
+
+        set_block_timestamp(STARTS_AT);
+        set_caller(&user(0), 0);
+        assert_eq!(status(&contract), RaffleStatus::Open);
+        mint(&mut contract, &user(1), 1);
+
+        set_block_timestamp(ENDS_AT);
+        set_caller(&user(0), 0);
+        assert_eq!(status(&contract), RaffleStatus::Closed);
+    }
+
+    #[test]
+    fn anyone_draws_the_configured_number_of_winners_once_ended() {
+        let mut contract = setup_timed_raffle(Some(2));
+        set_block_timestamp(ENDS_AT);
+        set_caller(&user(1), STORAGE_DEPOSIT);
+        let winners = contract.draw_winners(U64(RAFFLE_ID), None, None);
+        assert_eq!(winners.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Only the raffle owner can draw a raffle without a configured number of winners"
+    )]
+    fn anyone_needs_a_configured_number_of_winners() {
+        let mut contract = setup_timed_raffle(None);
+        set_block_timestamp(ENDS_AT);
+        set_caller(&user(1), STORAGE_DEPOSIT);
+        contract.draw_winners(U64(RAFFLE_ID), Some(3), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "only raffle owner")]
+    fn anyone_waits_for_the_raffle_to_end() {
+        let mut contract = setup_timed_raffle(Some(2));
+        set_block_timestamp(ENDS_AT - 1);
+        set_caller(&user(1), STORAGE_DEPOSIT);
+        contract.draw_winners(U64(RAFFLE_ID), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Number of winners must match the raffle's configuration")]
+    fn owner_cannot_override_the_configured_number_of_winners() {
+        let mut contract = setup_timed_raffle(Some(2));
+        set_block_timestamp(ENDS_AT);
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.draw_winners(U64(RAFFLE_ID), Some(1), None);
+    }
+
+    fn create(
+        contract: &mut Contract,
+        creator_id: &AccountId,
+        raffle_id: u64,
+        max_tickets: Option<u64>,
+    ) {
+        set_caller(creator_id, STORAGE_DEPOSIT);
+        contract.create_raffle(
+            raffle_id,
+            raffle_metadata(max_tickets),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    fn limit_creator(contract: &mut Contract, limits: CreatorLimits) {
+        set_caller(&contract_owner(), 0);
+        contract.set_creator_limits(raffle_owner(), limits);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not approved creator")]
+    fn only_approved_creators_create_raffles() {
+        let mut contract = setup_contract();
+        create(&mut contract, &user(0), RAFFLE_ID, None);
+    }
+
+    #[test]
+    fn finished_raffles_free_up_the_creators_quota() {
+        let mut contract = setup_contract();
+        limit_creator(
+            &mut contract,
+            CreatorLimits {
+                max_active_raffles: Some(1),
+                max_tickets_per_raffle: None,
+            },
+        );
+        create(&mut contract, &raffle_owner(), RAFFLE_ID, None);
+        assert_eq!(contract.get_active_raffles_for_creator(raffle_owner()), 1);
+
+        set_caller(&raffle_owner(), 0);
+        contract.cancel_raffle(U64(RAFFLE_ID));
+        assert_eq!(contract.get_active_raffles_for_creator(raffle_owner()), 0);
+        create(&mut contract, &raffle_owner(), RAFFLE_ID + 1, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Creator has too many active raffles")]
+    fn creators_stay_within_their_active_raffles() {
+        let mut contract = setup_contract();
+        limit_creator(
+            &mut contract,
+            CreatorLimits {
+                max_active_raffles: Some(1),
+                max_tickets_per_raffle: None,
+            },
+        );
+        create(&mut contract, &raffle_owner(), RAFFLE_ID, None);
+        create(&mut contract, &raffle_owner(), RAFFLE_ID + 1, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "max_tickets must be set and at most 10")]
+    fn creators_stay_within_their_ticket_limit() {
+        let mut contract = setup_contract();
+        limit_creator(
+            &mut contract,
+            CreatorLimits {
+                max_active_raffles: None,
+                max_tickets_per_raffle: Some(10),
+            },
+        );
+        create(&mut contract, &raffle_owner(), RAFFLE_ID, Some(10));
+        create(&mut contract, &raffle_owner(), RAFFLE_ID + 1, Some(11));
+    }
+
+    // Set up an open raffle where each account can hold at most two tickets
+    fn setup_capped_raffle() -> Contract {
+        let mut contract = setup_draft_raffle(RaffleMetadata {
+            max_tickets_per_account: Some(2),
+            ..raffle_metadata(None)
+        });
+        set_caller(&raffle_owner(), 0);
+        contract.open_raffle(U64(RAFFLE_ID));
+
+        contract
+    }
+
+    #[test]
+    #[should_panic(expected = "Account can hold at most 2 tickets for this raffle")]
+    fn minting_respects_the_per_account_cap() {
+        let mut contract = setup_capped_raffle();
+        mint(&mut contract, &user(0), 2);
+        mint(&mut contract, &user(0), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Account can hold at most 2 tickets for this raffle")]
+    fn transfers_respect_the_per_account_cap() {
+        let mut contract = setup_capped_raffle();
+        mint(&mut contract, &user(0), 2);
+        mint(&mut contract, &user(1), 1);
+
+        set_caller(&user(1), 1);
+        contract.nft_transfer(user(0), U64(2), None, None);
+    }
+
+    #[test]
+    fn transferring_a_ticket_away_frees_up_the_cap() {
+        let mut contract = setup_capped_raffle();
+        mint(&mut contract, &user(0), 2);
+
+        set_caller(&user(0), 1);
+        contract.nft_transfer(user(1), U64(0), None, None);
+        mint(&mut contract, &user(0), 1);
+        assert_eq!(contract.nft_supply_for_owner(user(0)).0, 2);
+    }
+
+    #[test]
+    fn first_mint_fills_a_capped_raffle() {
+        let mut contract = setup_draft_raffle(raffle_metadata(Some(2)));
+        set_caller(&raffle_owner(), 0);
+        contract.open_raffle(U64(RAFFLE_ID));
+        assert_eq!(remaining_tickets(&raffle(&contract)), Some(2));
+
+        mint(&mut contract, &user(0), 2);
+        assert_eq!(remaining_tickets(&raffle(&contract)), Some(0));
+        assert_eq!(raffle(&contract).metadata.live_tickets, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Must mint at least one ticket")]
+    fn minters_mint_at_least_one_ticket() {
+        let mut contract = setup_open_raffle();
+        mint(&mut contract, &user(0), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot mint any more tickets for the given raffle. Limit reached")]
+    fn minting_stops_at_max_tickets() {
+        let mut contract = setup_draft_raffle(raffle_metadata(Some(2)));
+        set_caller(&raffle_owner(), 0);
+        contract.open_raffle(U64(RAFFLE_ID));
+        mint(&mut contract, &user(0), 3);
+    }
+
+    #[test]
+    fn live_tickets_track_burns_and_draws() {
+        let mut contract = setup_open_raffle();
+        mint(&mut contract, &user(0), 3);
+
+        set_caller(&user(0), 1);
+        contract.nft_burn(U64(0));
+        assert_eq!(raffle(&contract).metadata.live_tickets, 2);
+
+        set_caller(&raffle_owner(), 0);
+        contract.close_raffle(U64(RAFFLE_ID));
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.draw_winners(U64(RAFFLE_ID), Some(1), None);
+        assert_eq!(raffle(&contract).metadata.live_tickets, 1);
+    }
+
+    fn mint_batch(contract: &mut Contract, receivers: Vec<(AccountId, u64)>) {
+        set_caller(&minter(), STORAGE_DEPOSIT);
+        contract.mint_tickets_batch(U64(RAFFLE_ID), receivers);
+    }
+
+    #[test]
+    fn batch_minting_merges_repeated_receivers() {
+        let mut contract = setup_open_raffle();
+        mint_batch(
+            &mut contract,
+            vec![(user(0), 1), (user(1), 1), (user(0), 1)],
+        );
+        assert_eq!(contract.nft_supply_for_owner(user(0)).0, 2);
+        assert_eq!(contract.nft_supply_for_owner(user(1)).0, 1);
+
+        // One mint log covering every receiver, with a single entry per owner
+        let logs = get_logs();
+        assert_eq!(logs.len(), 1);
+        assert!(logs[0].ends_with(&format!(
+            r#""data":[{{"owner_id":"{}","token_ids":["0","1"]}},{{"owner_id":"{}","token_ids":["2"]}}]}}"#,
+            user(0),
+            user(1)
+        )));
+    }
+
+    #[test]
+    #[should_panic(expected = "No receivers to mint to")]
+    fn batches_need_a_receiver() {
+        let mut contract = setup_open_raffle();
+        mint_batch(&mut contract, vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Must mint at least one ticket per receiver")]
+    fn batches_mint_at_least_one_ticket_per_receiver() {
+        let mut contract = setup_open_raffle();
+        mint_batch(&mut contract, vec![(user(0), 1), (user(1), 0)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many tickets in the batch. Split it into smaller batches")]
+    fn batch_totals_cannot_overflow() {
+        let mut contract = setup_open_raffle();
+        mint_batch(&mut contract, vec![(user(0), u64::MAX), (user(0), 1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many tickets in the batch. Split it into smaller batches")]
+    fn batch_gas_projection_cannot_overflow() {
+        let mut contract = setup_open_raffle();
+        mint_batch(&mut contract, vec![(user(0), u64::MAX)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Batch needs about")]
+    fn batches_must_fit_in_the_attached_gas() {
+        let mut contract = setup_open_raffle();
+        mint_batch(&mut contract, vec![(user(0), 1_000)]);
+    }
+
+    #[test]
+    fn derived_winners_survive_burned_tickets() {
+        let mut contract = setup_open_raffle();
+        mint(&mut contract, &user(0), 4);
+        set_caller(&raffle_owner(), 0);
+        contract.close_raffle(U64(RAFFLE_ID));
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        let winners = contract.draw_winners(U64(RAFFLE_ID), Some(2), None);
+        let winning_tickets: Vec<U64> = winners.iter().map(|winner| winner.ticket_id).collect();
+        assert_eq!(
+            contract.derive_winners(U64(RAFFLE_ID), None),
+            winning_tickets
+        );
+
+        // Burning a ticket reorders the raffle's tickets once it's settled
+        set_caller(&raffle_owner(), 0);
+        contract.settle_raffle(U64(RAFFLE_ID));
+        set_caller(&user(0), 1);
+        contract.nft_burn(U64(0));
+        assert_eq!(
+            contract.derive_winners(U64(RAFFLE_ID), None),
+            winning_tickets
+        );
+    }
+
+    const SECRET: &[u8] = b"owner's secret";
+
+    fn commit(contract: &mut Contract, secret: &[u8]) {
+        set_caller(&raffle_owner(), 0);
+        contract.commit_draw_secret(U64(RAFFLE_ID), Base64VecU8(env::sha256(secret)));
+    }
+
+    // Set up a closed raffle with four tickets, where the owner committed to SECRET
+    fn setup_committed_raffle() -> Contract {
+        let mut contract = setup_open_raffle();
+        mint(&mut contract, &user(0), 4);
+        commit(&mut contract, SECRET);
+        contract.close_raffle(U64(RAFFLE_ID));
+
+        contract
+    }
+
+    fn draw_with_secret(contract: &mut Contract, secret: Option<&[u8]>) -> Vec<Winner> {
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.draw_winners(
+            U64(RAFFLE_ID),
+            Some(2),
+            secret.map(|secret| Base64VecU8(secret.to_vec())),
+        )
+    }
+
+    #[test]
+    fn commit_reveal_draws_mix_the_secret_into_the_seed() {
+        let mut contract = setup_committed_raffle();
+        let winners = draw_with_secret(&mut contract, Some(SECRET));
+
+        let transcript = &contract.get_draw_rounds(U64(RAFFLE_ID))[0];
+        assert_eq!(
+            transcript.commitment.as_ref().unwrap().0,
+            env::sha256(SECRET)
+        );
+        assert_eq!(transcript.secret.as_ref().unwrap().0, SECRET);
+        assert_eq!(
+            transcript.seed.0,
+            env::sha256(&[transcript.block_seed.0.as_slice(), SECRET].concat())
+        );
+        assert!(raffle(&contract).draw_commitment.is_none());
+
+        let winning_tickets: Vec<U64> = winners.iter().map(|winner| winner.ticket_id).collect();
+        assert_eq!(
+            contract.derive_winners(U64(RAFFLE_ID), None),
+            winning_tickets
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Raffle uses commit-reveal. Must reveal the secret to draw")]
+    fn commit_reveal_draws_need_the_secret() {
+        let mut contract = setup_committed_raffle();
+        draw_with_secret(&mut contract, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Secret does not match the commitment")]
+    fn commit_reveal_draws_need_the_committed_secret() {
+        let mut contract = setup_committed_raffle();
+        draw_with_secret(&mut contract, Some(b"another secret"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Draw secret already committed")]
+    fn draw_secrets_are_committed_once() {
+        let mut contract = setup_committed_raffle();
+        commit(&mut contract, b"another secret");
+    }
+
+    #[test]
+    #[should_panic(expected = "Raffles anyone can draw can't use commit-reveal")]
+    fn permissionless_raffles_cannot_commit_a_secret() {
+        let mut contract = setup_timed_raffle(Some(1));
+        commit(&mut contract, SECRET);
+    }
+
+    // Overwrite the initial draw round's transcript of the raffle
+    fn tamper_with_transcript(contract: &mut Contract, tamper: impl FnOnce(&mut DrawTranscript)) {
+        let mut raffle = raffle(contract);
+        tamper(&mut raffle.draw_rounds[0]);
+        contract.raffle_by_id.insert(&RAFFLE_ID, &raffle);
+    }
+
+    #[test]
+    #[should_panic(expected = "Draw seed does not match its transcript")]
+    fn derived_winners_check_the_seed() {
+        let mut contract = setup_committed_raffle();
+        draw_with_secret(&mut contract, Some(SECRET));
+        tamper_with_transcript(&mut contract, |transcript| {
+            transcript.seed = Base64VecU8(env::sha256(b"another seed"))
+        });
+        contract.derive_winners(U64(RAFFLE_ID), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Revealed secret does not match the commitment")]
+    fn derived_winners_check_the_revealed_secret() {
+        let mut contract = setup_committed_raffle();
+        draw_with_secret(&mut contract, Some(SECRET));
+        tamper_with_transcript(&mut contract, |transcript| {
+            transcript.secret = Some(Base64VecU8(b"another secret".to_vec()))
+        });
+        contract.derive_winners(U64(RAFFLE_ID), None);
     }
 }
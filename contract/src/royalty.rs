@@ -1,7 +1,7 @@
 use near_sdk::json_types::U64;
 use crate::*;
 
-pub trait NonFungibleTokenCore {
+pub trait NonFungibleTokenRoyalty {
     //calculates the payout for a token given the passed in balance. This is a view method
     fn nft_payout(&self, token_id: U64, balance: U128, max_len_payout: u32) -> Payout;
 
@@ -18,7 +18,7 @@ pub trait NonFungibleTokenCore {
 }
 
 #[near_bindgen]
-impl NonFungibleTokenCore for Contract {
+impl NonFungibleTokenRoyalty for Contract {
     // Calculates the payout for a ticket given the passed-in balance. This is a view method.
     fn nft_payout(&self, ticket_id: U64, balance: U128, max_len_payout: u32) -> Payout {
         // Get the ticket object
@@ -43,7 +43,7 @@ impl NonFungibleTokenCore for Contract {
         if royalty_option.is_none() {
             let mut payout = HashMap::new();
             payout.insert(owner_id, balance);
-            return Payout { payout: payout };
+            return Payout { payout };
         }
         let royalty = royalty_option.unwrap();
 
@@ -122,7 +122,7 @@ impl NonFungibleTokenCore for Contract {
         if royalty_option.is_none() {
             let mut payout = HashMap::new();
             payout.insert(owner_id, balance);
-            return Payout { payout: payout };
+            return Payout { payout };
         }
         let royalty = royalty_option.unwrap();
 
@@ -0,0 +1,604 @@
+use crate::*;
+use near_sdk::{json_types::U64, Gas, PromiseResult};
+
+// Gas for forwarding fungible token proceeds to the beneficiary and resolving the transfer
+const GAS_FOR_FT_PROCEEDS_TRANSFER: Gas = Gas(15_000_000_000_000);
+const GAS_FOR_RESOLVE_PROCEEDS_TRANSFER: Gas = Gas(10_000_000_000_000);
+
+// Message passed to ft_on_transfer to buy tickets with fungible tokens.
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtTicketPurchase {
+    pub raffle_id: U64,
+    pub quantity: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+    // Put tickets for a raffle on sale at a fixed NEAR price, or take them off sale by passing None.
+    // Proceeds go to the beneficiary, or to the raffle owner if no beneficiary is given.
+    pub fn set_ticket_price(
+        &mut self,
+        raffle_id: U64,
+        ticket_price: Option<U128>,
+        beneficiary_id: Option<AccountId>,
+    ) {
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        self.assert_raffle_owner(&raffle);
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Draft | RaffleStatus::Open
+            ),
+            "Raffle is no longer selling tickets"
+        );
+        if raffle.beneficiary_id != beneficiary_id {
+            assert_no_proceeds(&raffle);
+        }
+
+        raffle.ticket_price = ticket_price.map(|price| price.0);
+        raffle.beneficiary_id = beneficiary_id;
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+    }
+
+    // Accept a fungible token for ticket purchases at a fixed price, or stop accepting one by passing None.
+    // Buyers pay with ft_transfer_call, after covering ticket storage with storage_deposit, and
+    // proceeds go to the same beneficiary as NEAR sales.
+    pub fn set_ft_ticket_price(&mut self, raffle_id: U64, ft_ticket_price: Option<FtTicketPrice>) {
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        self.assert_raffle_owner(&raffle);
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Draft | RaffleStatus::Open
+            ),
+            "Raffle is no longer selling tickets"
+        );
+        // The contract covers ticket storage for token purchases, so they can't be free
+        if let Some(ft_ticket_price) = ft_ticket_price.as_ref() {
+            require!(ft_ticket_price.price.0 > 0, "Ticket price must be positive");
+        }
+
+        raffle.ft_ticket_price = ft_ticket_price;
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+    }
+
+    // Split ticket sale proceeds with charities, in basis points per charity account.
+    // Whatever the charities don't get goes to the beneficiary. The split is locked once a ticket is sold.
+    pub fn set_charity_split(&mut self, raffle_id: U64, charity_split: HashMap<AccountId, u32>) {
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        self.assert_raffle_owner(&raffle);
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Draft | RaffleStatus::Open
+            ),
+            "Raffle is no longer selling tickets"
+        );
+        assert_no_proceeds(&raffle);
+        let total: u64 = charity_split.values().map(|bps| u64::from(*bps)).sum();
+        require!(
+            total <= 10_000,
+            "Charity split cannot exceed 10000 basis points"
+        );
+
+        raffle.charity_split = charity_split;
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+    }
+
+    // Buy tickets for an open raffle. The attached deposit must cover the ticket price
+    // as well as the storage for the new tickets. Anything left over is refunded.
+    #[payable]
+    pub fn buy_tickets(&mut self, raffle_id: U64, amount: u64) -> Vec<U64> {
+        require!(amount > 0, "Must buy at least one ticket");
+
+        // Measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        let ticket_price = raffle
+            .ticket_price
+            .expect("Tickets for this raffle are not for sale");
+        let proceeds = ticket_price
+            .checked_mul(Balance::from(amount))
+            .expect("Ticket price overflow");
+
+        let buyer_id = env::predecessor_account_id();
+        let tickets = self.internal_mint_tickets(
+            raffle_id.0,
+            &mut raffle,
+            &buyer_id,
+            amount,
+            Some(&buyer_id),
+        );
+
+        // Hold the proceeds until the raffle is drawn so buyers can be refunded if it's cancelled
+        for ticket_id in tickets.iter() {
+            self.ticket_payments.insert(
+                ticket_id,
+                &TicketPayment {
+                    token_id: None,
+                    amount: U128(ticket_price),
+                },
+            );
+        }
+        raffle.near_proceeds += proceeds;
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+        log_nft_mint(&[(buyer_id.clone(), tickets.clone())]);
+
+        // The buyer pays for the tickets on top of the storage they use
+        let storage_used = env::storage_usage() - initial_storage_usage;
+        let required_cost = proceeds + env::storage_byte_cost() * Balance::from(storage_used);
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            required_cost <= attached_deposit,
+            "Must attach {} yoctoNEAR to cover the tickets and storage",
+            required_cost,
+        );
+
+        let refund = attached_deposit - required_cost;
+        if refund > 1 {
+            Promise::new(buyer_id.clone()).transfer(refund);
+        }
+
+        // Record the donation to the charities and the beneficiary
+        log_donation_receipt(
+            raffle_id.0,
+            &buyer_id,
+            None,
+            &proceeds_payouts(&raffle, proceeds),
+        );
+
+        tickets.into_iter().map(U64).collect()
+    }
+
+    // Pay out the ticket sale proceeds held for a raffle to its charities and beneficiary, along
+    // with any earlier payouts that failed.
+    // Proceeds are only released once the raffle has been drawn, so anyone can call this.
+    pub fn withdraw_proceeds(&mut self, raffle_id: U64) {
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        require!(
+            matches!(
+                raffle_status(&raffle),
+                RaffleStatus::Drawn | RaffleStatus::Settled
+            ),
+            "Proceeds are held until the raffle is drawn"
+        );
+
+        // Take everything owed out of the raffle before paying it so it can't be paid twice
+        let near_proceeds = std::mem::take(&mut raffle.near_proceeds);
+        let ft_proceeds = std::mem::take(&mut raffle.ft_proceeds);
+        let mut payouts = std::mem::take(&mut raffle.unpaid_proceeds);
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+
+        for (recipient_id, share) in proceeds_payouts(&raffle, near_proceeds) {
+            payouts.push(UnpaidProceeds {
+                recipient_id,
+                token_id: None,
+                amount: U128(share),
+            });
+        }
+        for (token_id, amount) in ft_proceeds {
+            for (recipient_id, share) in proceeds_payouts(&raffle, amount) {
+                payouts.push(UnpaidProceeds {
+                    recipient_id,
+                    token_id: Some(token_id.clone()),
+                    amount: U128(share),
+                });
+            }
+        }
+        require!(!payouts.is_empty(), "No proceeds to withdraw");
+
+        for payout in payouts {
+            let transfer = match payout.token_id.as_ref() {
+                None => Promise::new(payout.recipient_id.clone()).transfer(payout.amount.0),
+                Some(token_id) => ext_ft_prize::ext(token_id.clone())
+                    .with_attached_deposit(1)
+                    .with_static_gas(GAS_FOR_FT_PROCEEDS_TRANSFER)
+                    .ft_transfer(
+                        payout.recipient_id.clone(),
+                        payout.amount,
+                        Some(format!("Raffle {} ticket sales", raffle_id.0)),
+                    ),
+            };
+            transfer.then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_PROCEEDS_TRANSFER)
+                    .resolve_proceeds_withdrawal(raffle_id, payout),
+            );
+        }
+    }
+
+    // Resolve a payout from withdraw_proceeds. If it failed, the payout is kept for the next withdrawal.
+    #[private]
+    pub fn resolve_proceeds_withdrawal(&mut self, raffle_id: U64, payout: UnpaidProceeds) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+
+        let mut raffle = self.raffle_by_id.get(&raffle_id.0).expect("Not a raffle");
+        raffle.unpaid_proceeds.push(payout);
+        self.raffle_by_id.insert(&raffle_id.0, &raffle);
+
+        false
+    }
+
+    // Deposit NEAR to cover the storage of tickets bought with fungible tokens, since
+    // ft_transfer_call can't attach NEAR. The first deposit pays for its own entry.
+    // Returns the account's storage balance.
+    #[payable]
+    pub fn storage_deposit(&mut self, account_id: Option<AccountId>) -> U128 {
+        // Measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let deposit = env::attached_deposit();
+        let balance = self.storage_deposits.get(&account_id).unwrap_or(0);
+        self.storage_deposits
+            .insert(&account_id, &(balance + deposit));
+
+        let entry_cost =
+            env::storage_byte_cost() * Balance::from(env::storage_usage() - initial_storage_usage);
+        require!(
+            deposit > entry_cost,
+            format!("Must attach more than {} yoctoNEAR", entry_cost)
+        );
+        let balance = balance + deposit - entry_cost;
+        self.storage_deposits.insert(&account_id, &balance);
+
+        U128(balance)
+    }
+
+    // Withdraw the caller's whole storage balance, along with the storage its entry used
+    #[payable]
+    pub fn storage_withdraw(&mut self) -> U128 {
+        // Ensure the user attached exactly 1 yoctoNEAR for security and wallet redirection.
+        assert_one_yocto();
+
+        // Measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        let account_id = env::predecessor_account_id();
+        let balance = self
+            .storage_deposits
+            .remove(&account_id)
+            .expect("No storage balance to withdraw");
+        let entry_refund =
+            env::storage_byte_cost() * Balance::from(initial_storage_usage - env::storage_usage());
+        Promise::new(account_id).transfer(balance + entry_refund + 1);
+
+        U128(balance)
+    }
+
+    // Get how much NEAR an account has left for the storage of tickets bought with fungible tokens
+    pub fn storage_balance_of(&self, account_id: AccountId) -> U128 {
+        U128(self.storage_deposits.get(&account_id).unwrap_or(0))
+    }
+
+    // Get what was paid for a ticket, if it was bought through buy_tickets or ft_transfer_call
+    pub fn get_ticket_payment(&self, token_id: U64) -> Option<TicketPayment> {
+        self.ticket_payments.get(&token_id.0)
+    }
+}
+
+impl Contract {
+    // Mint tickets paid for with fungible tokens sent through ft_transfer_call.
+    // Returns the part of the amount that wasn't needed so the token contract refunds it.
+    pub(crate) fn internal_buy_tickets_with_ft(
+        &mut self,
+        sender_id: AccountId,
+        token_id: AccountId,
+        amount: U128,
+        purchase: FtTicketPurchase,
+    ) -> PromiseOrValue<U128> {
+        require!(purchase.quantity > 0, "Must buy at least one ticket");
+
+        let raffle_id = purchase.raffle_id.0;
+        let mut raffle = self.raffle_by_id.get(&raffle_id).expect("Not a raffle");
+        let ft_ticket_price = raffle
+            .ft_ticket_price
+            .clone()
+            .expect("Tickets for this raffle are not for sale in fungible tokens");
+        require!(
+            ft_ticket_price.token_id == token_id,
+            "Raffle does not accept this token"
+        );
+
+        let cost = ft_ticket_price
+            .price
+            .0
+            .checked_mul(u128::from(purchase.quantity))
+            .expect("Ticket price overflow");
+        require!(cost <= amount.0, "Not enough tokens to buy the tickets");
+
+        // Measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        let tickets = self.internal_mint_tickets(
+            raffle_id,
+            &mut raffle,
+            &sender_id,
+            purchase.quantity,
+            Some(&sender_id),
+        );
+
+        // Hold the proceeds until the raffle is drawn so buyers can be refunded if it's cancelled
+        for ticket_id in tickets.iter() {
+            self.ticket_payments.insert(
+                ticket_id,
+                &TicketPayment {
+                    token_id: Some(token_id.clone()),
+                    amount: ft_ticket_price.price,
+                },
+            );
+        }
+        *raffle.ft_proceeds.entry(token_id.clone()).or_insert(0) += cost;
+        self.raffle_by_id.insert(&raffle_id, &raffle);
+
+        // Tokens can't cover storage, so it comes out of the buyer's storage deposit instead
+        let storage_used = env::storage_usage().saturating_sub(initial_storage_usage);
+        let storage_cost = env::storage_byte_cost() * Balance::from(storage_used);
+        let storage_balance = self.storage_deposits.get(&sender_id).unwrap_or(0);
+        require!(
+            storage_cost <= storage_balance,
+            format!(
+                "Must deposit {} yoctoNEAR for storage with storage_deposit to buy these tickets",
+                storage_cost
+            )
+        );
+        self.storage_deposits
+            .insert(&sender_id, &(storage_balance - storage_cost));
+        log_nft_mint(&[(sender_id.clone(), tickets)]);
+
+        // Record the donation to the charities and the beneficiary
+        log_donation_receipt(
+            raffle_id,
+            &sender_id,
+            Some(&token_id),
+            &proceeds_payouts(&raffle, cost),
+        );
+
+        PromiseOrValue::Value(U128(amount.0 - cost))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    const TICKET_PRICE: Balance = 1_000_000_000_000_000_000_000;
+
+    // Set up an open raffle selling tickets at TICKET_PRICE
+    fn setup_priced_raffle() -> Contract {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), 0);
+        contract.set_ticket_price(U64(RAFFLE_ID), Some(U128(TICKET_PRICE)), None);
+
+        contract
+    }
+
+    #[test]
+    fn buying_tickets_holds_the_proceeds() {
+        let mut contract = setup_priced_raffle();
+        set_caller(&user(0), 2 * TICKET_PRICE + STORAGE_DEPOSIT);
+        let tickets = contract.buy_tickets(U64(RAFFLE_ID), 2);
+
+        assert_eq!(tickets, vec![U64(0), U64(1)]);
+        assert_eq!(contract.nft_supply_for_owner(user(0)).0, 2);
+        assert_eq!(raffle(&contract).near_proceeds, 2 * TICKET_PRICE);
+        let payment = contract.get_ticket_payment(U64(0)).unwrap();
+        assert_eq!(payment.token_id, None);
+        assert_eq!(payment.amount.0, TICKET_PRICE);
+    }
+
+    #[test]
+    #[should_panic(expected = "to cover the tickets and storage")]
+    fn buyers_pay_for_every_ticket() {
+        let mut contract = setup_priced_raffle();
+        set_caller(&user(0), 2 * TICKET_PRICE);
+        contract.buy_tickets(U64(RAFFLE_ID), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Tickets for this raffle are not for sale")]
+    fn only_priced_raffles_sell_tickets() {
+        let mut contract = setup_open_raffle();
+        set_caller(&user(0), STORAGE_DEPOSIT);
+        contract.buy_tickets(U64(RAFFLE_ID), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Raffle is not open for minting")]
+    fn closed_raffles_stop_selling_tickets() {
+        let mut contract = setup_priced_raffle();
+        contract.close_raffle(U64(RAFFLE_ID));
+        set_caller(&user(0), TICKET_PRICE + STORAGE_DEPOSIT);
+        contract.buy_tickets(U64(RAFFLE_ID), 1);
+    }
+
+    fn charity() -> AccountId {
+        "charity.near".parse().unwrap()
+    }
+
+    // Set up a drawn raffle where user(0) bought two tickets and a quarter of the proceeds go to charity
+    fn setup_drawn_raffle() -> Contract {
+        let mut contract = setup_priced_raffle();
+        contract.set_charity_split(U64(RAFFLE_ID), HashMap::from([(charity(), 2_500)]));
+        set_caller(&user(0), 2 * TICKET_PRICE + STORAGE_DEPOSIT);
+        contract.buy_tickets(U64(RAFFLE_ID), 2);
+        set_caller(&raffle_owner(), 0);
+        contract.close_raffle(U64(RAFFLE_ID));
+        set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+        contract.draw_winners(U64(RAFFLE_ID), Some(1), None);
+
+        contract
+    }
+
+    #[test]
+    #[should_panic(expected = "Proceeds split can't change once tickets have been sold")]
+    fn charity_split_is_locked_after_a_sale() {
+        let mut contract = setup_priced_raffle();
+        set_caller(&user(0), TICKET_PRICE + STORAGE_DEPOSIT);
+        contract.buy_tickets(U64(RAFFLE_ID), 1);
+
+        set_caller(&raffle_owner(), 0);
+        contract.set_charity_split(U64(RAFFLE_ID), HashMap::from([(charity(), 2_500)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Proceeds split can't change once tickets have been sold")]
+    fn beneficiary_is_locked_after_a_sale() {
+        let mut contract = setup_priced_raffle();
+        set_caller(&user(0), TICKET_PRICE + STORAGE_DEPOSIT);
+        contract.buy_tickets(U64(RAFFLE_ID), 1);
+
+        set_caller(&raffle_owner(), 0);
+        contract.set_ticket_price(U64(RAFFLE_ID), Some(U128(TICKET_PRICE)), Some(charity()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Proceeds are held until the raffle is drawn")]
+    fn proceeds_are_held_until_the_draw() {
+        let mut contract = setup_priced_raffle();
+        set_caller(&user(0), TICKET_PRICE + STORAGE_DEPOSIT);
+        contract.buy_tickets(U64(RAFFLE_ID), 1);
+        contract.withdraw_proceeds(U64(RAFFLE_ID));
+    }
+
+    #[test]
+    fn failed_payouts_are_withdrawn_again() {
+        let mut contract = setup_drawn_raffle();
+        set_caller(&user(1), 0);
+        contract.withdraw_proceeds(U64(RAFFLE_ID));
+        assert_eq!(raffle(&contract).near_proceeds, 0);
+
+        // The charity's payout fails and is kept for the next withdrawal
+        let payout = UnpaidProceeds {
+            token_id: None,
+            recipient_id: charity(),
+            amount: U128(TICKET_PRICE / 2),
+        };
+        set_callback_result(PromiseResult::Failed);
+        assert!(!contract.resolve_proceeds_withdrawal(U64(RAFFLE_ID), payout));
+        assert_eq!(raffle(&contract).unpaid_proceeds.len(), 1);
+
+        set_caller(&user(1), 0);
+        contract.withdraw_proceeds(U64(RAFFLE_ID));
+        assert!(raffle(&contract).unpaid_proceeds.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "No proceeds to withdraw")]
+    fn proceeds_are_withdrawn_once() {
+        let mut contract = setup_drawn_raffle();
+        set_caller(&user(1), 0);
+        contract.withdraw_proceeds(U64(RAFFLE_ID));
+        contract.withdraw_proceeds(U64(RAFFLE_ID));
+    }
+
+    const FT_TICKET_PRICE: Balance = 100;
+
+    // Set up an open raffle selling tickets for FT_TICKET_PRICE of the prize token, where user(0)
+    // has deposited for storage
+    fn setup_ft_priced_raffle() -> Contract {
+        let mut contract = setup_open_raffle();
+        set_caller(&raffle_owner(), 0);
+        contract.set_ft_ticket_price(
+            U64(RAFFLE_ID),
+            Some(FtTicketPrice {
+                token_id: prize_token(),
+                price: U128(FT_TICKET_PRICE),
+            }),
+        );
+        set_caller(&user(0), STORAGE_DEPOSIT);
+        contract.storage_deposit(None);
+
+        contract
+    }
+
+    // Buy tickets for user(0) by sending `amount` of `token_id`, returning the unused amount
+    fn buy_with_tokens(
+        contract: &mut Contract,
+        token_id: &AccountId,
+        amount: Balance,
+        quantity: u64,
+    ) -> Balance {
+        set_caller(token_id, 0);
+        let msg = format!(
+            r#"{{"raffle_id": "{}", "quantity": {}}}"#,
+            RAFFLE_ID, quantity
+        );
+        match contract.ft_on_transfer(user(0), U128(amount), msg) {
+            PromiseOrValue::Value(unused) => unused.0,
+            PromiseOrValue::Promise(_) => panic!("Expected the unused amount"),
+        }
+    }
+
+    #[test]
+    fn buying_tickets_with_tokens_returns_the_unused_amount() {
+        let mut contract = setup_ft_priced_raffle();
+        let storage_balance = contract.storage_balance_of(user(0)).0;
+        let unused = buy_with_tokens(&mut contract, &prize_token(), 250, 2);
+
+        assert_eq!(unused, 50);
+        assert_eq!(contract.nft_supply_for_owner(user(0)).0, 2);
+        assert_eq!(raffle(&contract).ft_proceeds[&prize_token()], 200);
+        let payment = contract.get_ticket_payment(U64(0)).unwrap();
+        assert_eq!(payment.token_id, Some(prize_token()));
+        assert_eq!(payment.amount.0, FT_TICKET_PRICE);
+        // The buyer's storage deposit paid for the tickets
+        assert!(contract.storage_balance_of(user(0)).0 < storage_balance);
+        assert_eq!(contract.storage_payer_by_ticket.get(&0), Some(user(0)));
+    }
+
+    #[test]
+    #[should_panic(expected = "for storage with storage_deposit to buy these tickets")]
+    fn buying_tickets_with_tokens_needs_a_storage_deposit() {
+        let mut contract = setup_ft_priced_raffle();
+        set_caller(&user(0), 1);
+        contract.storage_withdraw();
+        buy_with_tokens(&mut contract, &prize_token(), 100, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Raffle does not accept this token")]
+    fn tickets_are_only_sold_for_the_accepted_token() {
+        let mut contract = setup_ft_priced_raffle();
+        buy_with_tokens(&mut contract, &"fake.near".parse().unwrap(), 100, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Must buy at least one ticket")]
+    fn token_purchases_buy_at_least_one_ticket() {
+        let mut contract = setup_ft_priced_raffle();
+        buy_with_tokens(&mut contract, &prize_token(), 100, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough tokens to buy the tickets")]
+    fn token_purchases_cover_every_ticket() {
+        let mut contract = setup_ft_priced_raffle();
+        buy_with_tokens(&mut contract, &prize_token(), 150, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Raffle is not open for minting")]
+    fn closed_raffles_stop_selling_tickets_for_tokens() {
+        let mut contract = setup_ft_priced_raffle();
+        set_caller(&raffle_owner(), 0);
+        contract.close_raffle(U64(RAFFLE_ID));
+        buy_with_tokens(&mut contract, &prize_token(), 100, 1);
+    }
+
+    #[test]
+    fn storage_deposits_are_withdrawn() {
+        let mut contract = setup_ft_priced_raffle();
+        let storage_balance = contract.storage_balance_of(user(0)).0;
+        assert!(storage_balance > 0);
+
+        set_caller(&user(0), 1);
+        assert_eq!(contract.storage_withdraw().0, storage_balance);
+        assert_eq!(contract.storage_balance_of(user(0)).0, 0);
+    }
+}
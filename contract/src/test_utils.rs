@@ -0,0 +1,145 @@
+// Shared setup for the unit tests: a contract with one raffle, owned by an approved creator.
+use crate::*;
+use near_sdk::json_types::U64;
+use near_sdk::test_utils::{accounts, VMContextBuilder};
+use near_sdk::{testing_env, PromiseResult, RuntimeFeesConfig, VMConfig};
+use std::cell::Cell;
+
+thread_local! {
+    // Block timestamp every following call runs at
+    static BLOCK_TIMESTAMP: Cell<u64> = const { Cell::new(0) };
+}
+
+pub(crate) const RAFFLE_ID: u64 = 1;
+// Enough to cover the storage of anything the tests do in a single call
+pub(crate) const STORAGE_DEPOSIT: Balance = 1_000_000_000_000_000_000_000_000;
+
+pub(crate) fn contract_id() -> AccountId {
+    "raffle.near".parse().unwrap()
+}
+
+pub(crate) fn contract_owner() -> AccountId {
+    accounts(0)
+}
+
+pub(crate) fn raffle_owner() -> AccountId {
+    accounts(1)
+}
+
+pub(crate) fn minter() -> AccountId {
+    accounts(2)
+}
+
+pub(crate) fn user(index: usize) -> AccountId {
+    accounts(3 + index)
+}
+
+// Token contract used for fungible token prizes
+pub(crate) fn prize_token() -> AccountId {
+    "usdc.near".parse().unwrap()
+}
+
+// NFT contract used for NFT prizes
+pub(crate) fn nft_contract() -> AccountId {
+    "nft.near".parse().unwrap()
+}
+
+// Run every following call at the given block timestamp (in nanoseconds)
+pub(crate) fn set_block_timestamp(block_timestamp: u64) {
+    BLOCK_TIMESTAMP.with(|now| now.set(block_timestamp));
+}
+
+// Context for a call to the contract from `predecessor` with `deposit` attached
+pub(crate) fn context(predecessor: &AccountId, deposit: Balance) -> VMContextBuilder {
+    let mut builder = VMContextBuilder::new();
+    builder
+        .current_account_id(contract_id())
+        .signer_account_id(predecessor.clone())
+        .predecessor_account_id(predecessor.clone())
+        .attached_deposit(deposit)
+        .block_timestamp(BLOCK_TIMESTAMP.with(Cell::get));
+    builder
+}
+
+// Make the next call come from `predecessor` with `deposit` attached
+pub(crate) fn set_caller(predecessor: &AccountId, deposit: Balance) {
+    testing_env!(context(predecessor, deposit).build());
+}
+
+// Make the next call a callback from the contract to itself that received `result`
+pub(crate) fn set_callback_result(result: PromiseResult) {
+    testing_env!(
+        context(&contract_id(), 0).build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        vec![result],
+    );
+}
+
+pub(crate) fn raffle_metadata(max_tickets: Option<u64>) -> RaffleMetadata {
+    RaffleMetadata {
+        title: "Raffle".to_string(),
+        description: "A raffle".to_string(),
+        media: "https://example.com/raffle.png".to_string(),
+        max_tickets,
+        live_tickets: 0,
+        max_tickets_per_account: None,
+    }
+}
+
+// Set up a contract where the raffle owner is an approved creator, along with an approved minter
+pub(crate) fn setup_contract() -> Contract {
+    set_caller(&contract_owner(), 0);
+    let mut contract = Contract::new_default_meta(contract_owner());
+    contract.add_approved_creator(raffle_owner());
+    contract.add_approved_minter(minter());
+
+    contract
+}
+
+// Set up a contract with an approved minter and a draft raffle with the given metadata
+pub(crate) fn setup_draft_raffle(raffle_metadata: RaffleMetadata) -> Contract {
+    let mut contract = setup_contract();
+    set_caller(&raffle_owner(), STORAGE_DEPOSIT);
+    contract.create_raffle(
+        RAFFLE_ID,
+        raffle_metadata,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    );
+
+    contract
+}
+
+// Set up a contract with an approved minter and an open raffle without a ticket cap
+pub(crate) fn setup_open_raffle() -> Contract {
+    let mut contract = setup_draft_raffle(raffle_metadata(None));
+    set_caller(&raffle_owner(), 0);
+    contract.open_raffle(U64(RAFFLE_ID));
+
+    contract
+}
+
+// Mint tickets to the receiver through the approved minter
+pub(crate) fn mint(contract: &mut Contract, receiver_id: &AccountId, ticket_amount: u64) {
+    set_caller(&minter(), STORAGE_DEPOSIT);
+    contract.mint_ticket(
+        U64(RAFFLE_ID),
+        receiver_id.clone(),
+        ticket_amount,
+        None,
+        None,
+        None,
+        None,
+    );
+}
+
+pub(crate) fn raffle(contract: &Contract) -> Raffle {
+    contract.raffle_by_id.get(&RAFFLE_ID).unwrap()
+}